@@ -47,21 +47,18 @@ impl<'a, 'b> MainState<'a, 'b> {
     fn new() -> GameResult<MainState<'a, 'b>> {
         let mut world = World::new();
 
-        let mut dispatcher = DispatcherBuilder::new();
-        let init_funcs = [
-            init,
-            viewport::init,
-            collision::init,
-            bounce::init,
-            health_damage::init,
-            simple_physics::init,
-            position_motion::init,
-            sprites::init,
-            despawn::init,
-        ];
-        for init_func in init_funcs.iter() {
-            dispatcher = init_func(&mut world, dispatcher);
-        }
+        let dispatcher = PluginSet::new()
+            .with(CorePlugin)
+            .with(rng::RngPlugin::new(rng::random_seed()))
+            .with(viewport::ViewportPlugin)
+            .with(collision::CollisionPlugin)
+            .with(bounce::BouncePlugin)
+            .with(health_damage::HealthDamagePlugin)
+            .with(simple_physics::SimplePhysicsPlugin)
+            .with(position_motion::PositionMotionPlugin)
+            .with(sprites::SpritesPlugin)
+            .with(despawn::DespawnPlugin)
+            .build(&mut world)?;
 
         for _idx in 0..10 {
             spawn_asteroid(&mut world);
@@ -118,15 +115,11 @@ fn spawn_asteroid(world: &mut World) {
 
     let size = 25.0 + 150.0 * rand::random::<f32>();
 
-    {
-        let positions = world.read::<position_motion::Position>();
-        let collidables = world.read::<collision::Collidable>();
-        if !collision::is_empty_at(&positions, &collidables, x, y, size) {
-            return;
-        }
+    if !collision::is_empty_at(&world.read_resource(), x, y, size) {
+        return;
     }
 
-    world
+    let asteroid = world
         .create_entity()
         .with(position_motion::Position {
             x,
@@ -139,7 +132,7 @@ fn spawn_asteroid(world: &mut World) {
             ..Default::default()
         })
         .with(despawn::Timeout(7.0))
-        .with(collision::Collidable { size })
+        .with(collision::Collidable::circle(size))
         .with(bounce::BounceOnCollision {
             ..Default::default()
         })
@@ -149,10 +142,7 @@ fn spawn_asteroid(world: &mut World) {
             ..Default::default()
         })
         .with(despawn::DespawnBounds(Rect::new(
-            -800.0,
-            -450.0,
-            1600.0,
-            900.0,
+            -800.0, -450.0, 1600.0, 900.0,
         )))
         //.with(health_damage::DamageOnCollision {
         //    damage: 100.0,
@@ -161,4 +151,13 @@ fn spawn_asteroid(world: &mut World) {
         //.with(health_damage::Health(100.0))
         //.with(despawn::Tombstone)
         .build();
+
+    // Several can spawn before the index is next rebuilt, so keep them from spawning on top of
+    // each other.
+    world.write_resource::<collision::SpatialIndex>().insert(
+        asteroid,
+        x,
+        y,
+        &collision::Collidable::circle(size),
+    );
 }
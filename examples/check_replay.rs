@@ -0,0 +1,63 @@
+extern crate ggez;
+extern crate invaders;
+
+use std::env;
+use std::process;
+use ggez::*;
+use invaders::plugins::*;
+use invaders::simulation::Simulation;
+
+// Plays a replay file back without a window and checks that it ends on the score it was recorded
+// with, e.g. to confirm a replay attached to a bug report still reproduces.
+pub fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            println!("Usage: check_replay <replay file>");
+            process::exit(2);
+        }
+    };
+    match run(&path) {
+        Ok(true) => println!("OK"),
+        Ok(false) => process::exit(1),
+        Err(e) => {
+            println!("Replay failed: {}", e);
+            process::exit(2);
+        }
+    }
+}
+
+fn run(path: &str) -> GameResult<bool> {
+    let recording = replay::Recording::load(path)?;
+    if recording.version != replay::BUILD_VERSION {
+        println!(
+            "Recorded with version {}, checking with {}",
+            recording.version,
+            replay::BUILD_VERSION
+        );
+    }
+    println!(
+        "Seed: {} ticks: {} expected score: {}",
+        recording.seed,
+        recording.frames.len(),
+        recording.final_score
+    );
+
+    let ticks = recording.frames.len();
+    let expected_score = recording.final_score;
+    let config = invaders::config::GameConfig::parse(&recording.config, &[])?;
+    let mut simulation = Simulation::new(invaders::game_plugins(recording.seed, config))?;
+    invaders::start_playback(&mut simulation, recording)?;
+    for _ in 0..ticks {
+        simulation.step(invaders::simulation::FIXED_STEP)?;
+    }
+
+    let final_score = simulation.world.read_resource::<score::PlayerScore>().get();
+    if final_score != expected_score {
+        println!(
+            "Score mismatch: expected {} but got {}",
+            expected_score, final_score
+        );
+    }
+    Ok(final_score == expected_score)
+}
@@ -0,0 +1,126 @@
+extern crate ggez;
+extern crate invaders;
+extern crate rand;
+extern crate specs;
+
+use std::env;
+use std::time::{Duration, Instant};
+use rand::{Rng, SeedableRng, XorShiftRng};
+use ggez::*;
+use specs::*;
+use invaders::*;
+use invaders::plugins::*;
+
+// Times collision detection through the spatial index against comparing every pair, for a field
+// of asteroids that grows with their number so crowding stays about what it is in the game. Pass
+// the counts to try, e.g. `collision_bench 1000 5000 20000`. Build with --release for real numbers.
+pub fn main() {
+    let counts: Vec<usize> = env::args()
+        .skip(1)
+        .filter_map(|arg| arg.parse().ok())
+        .collect();
+    let counts = if counts.is_empty() {
+        vec![100, 500, 1000, 2000, 5000, 10000]
+    } else {
+        counts
+    };
+    println!(
+        "{:>8} {:>12} {:>12} {:>10}",
+        "count", "index ms", "all-pairs ms", "collisions"
+    );
+    for count in counts {
+        if let Err(e) = run(count) {
+            println!("Benchmark failed: {}", e);
+            return;
+        }
+    }
+}
+
+// Game-sized crowding: about 1 asteroid per 40,000 square units.
+const AREA_PER_ASTEROID: f32 = 40_000.0;
+const TICKS: u32 = 20;
+// Past this the all-pairs check takes too long to be worth waiting for.
+const MAX_ALL_PAIRS: usize = 10_000;
+
+fn run(count: usize) -> GameResult<()> {
+    let mut world = World::new();
+    PluginSet::new()
+        .with(CorePlugin)
+        .with(position_motion::PositionMotionPlugin)
+        .with(collision::CollisionPlugin)
+        .build(&mut world)?;
+
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, count as u32]);
+    let side = (count as f32 * AREA_PER_ASTEROID).sqrt();
+    for _ in 0..count {
+        world
+            .create_entity()
+            .with(position_motion::Position {
+                x: side * rng.gen::<f32>() - side / 2.0,
+                y: side * rng.gen::<f32>() - side / 2.0,
+                ..Default::default()
+            })
+            .with(collision::Collidable::circle(
+                25.0 + 150.0 * rng.gen::<f32>(),
+            ))
+            .build();
+    }
+
+    let start = Instant::now();
+    for _ in 0..TICKS {
+        collision::SpatialIndexSystem.run_now(&world.res);
+        collision::CollisionSystem.run_now(&world.res);
+    }
+    let index_ms = millis(start.elapsed()) / TICKS as f32;
+    let collisions = world.read_resource::<collision::Collisions>().len();
+
+    let all_pairs = if count <= MAX_ALL_PAIRS {
+        let start = Instant::now();
+        let found = all_pairs(&world);
+        if found != collisions {
+            println!(
+                "Mismatch at {}: index found {} colliding, all pairs found {}",
+                count, collisions, found
+            );
+        }
+        format!("{:.3}", millis(start.elapsed()))
+    } else {
+        String::from("-")
+    };
+
+    println!(
+        "{:>8} {:>12.3} {:>12} {:>10}",
+        count, index_ms, all_pairs, collisions
+    );
+    Ok(())
+}
+
+// What CollisionSystem used to do. Returns how many entities collide with something.
+fn all_pairs(world: &World) -> usize {
+    let entities = world.entities();
+    let positions = world.read::<position_motion::Position>();
+    let collidables = world.read::<collision::Collidable>();
+    let mut colliding = 0;
+    for (entity, pos, col) in (&*entities, &positions, &collidables).join() {
+        for (other_entity, other_pos, other_col) in (&*entities, &positions, &collidables).join() {
+            if entity != other_entity
+                && collision::overlaps(
+                    pos.x,
+                    pos.y,
+                    col.size(),
+                    other_pos.x,
+                    other_pos.y,
+                    other_col.size(),
+                )
+            {
+                colliding += 1;
+                break;
+            }
+        }
+    }
+    colliding
+}
+
+fn millis(duration: Duration) -> f32 {
+    duration.as_secs() as f32 * 1000.0 + duration.subsec_nanos() as f32 * 1e-6
+}
@@ -0,0 +1,67 @@
+extern crate ggez;
+extern crate invaders;
+
+use std::env;
+use ggez::*;
+use invaders::game::{GameMode, GameModeManager};
+use invaders::plugins::*;
+use invaders::simulation::Simulation;
+
+// Plays a whole session without opening a window: sit in attract mode for a bit, press fire to
+// start, then hold fire until the game ends and report the final score. Pass a seed as the first
+// argument to play a different session; the same seed always gives the same score.
+pub fn main() {
+    let seed = env::args()
+        .nth(1)
+        .and_then(|arg| arg.parse().ok())
+        .unwrap_or(1);
+    if let Err(e) = run(seed) {
+        println!("Simulation failed: {}", e);
+    }
+}
+
+fn run(seed: u64) -> GameResult<()> {
+    println!("Seed: {}", seed);
+    let mut simulation = Simulation::new(invaders::game_plugins(
+        seed,
+        invaders::config::GameConfig::default(),
+    ))?;
+
+    simulation.run_for(2.0)?;
+    report(&simulation, "attract");
+
+    // Fire starts the game, but starting the game also resets inputs - so press it again.
+    press_fire(&mut simulation);
+    simulation.run_for(0.1)?;
+    press_fire(&mut simulation);
+
+    let max_seconds = 600.0;
+    while simulation.elapsed < max_seconds {
+        simulation.run_for(1.0)?;
+        let game_mode = simulation.world.read_resource::<GameModeManager>();
+        if game_mode.is_current(GameMode::GameOver) {
+            break;
+        }
+    }
+    report(&simulation, "finished");
+
+    Ok(())
+}
+
+fn press_fire(simulation: &mut Simulation) {
+    simulation
+        .world
+        .write_resource::<player_control::Inputs>()
+        .fire = true;
+}
+
+fn report(simulation: &Simulation, label: &str) {
+    let player_score = simulation.world.read_resource::<score::PlayerScore>();
+    println!(
+        "[{}] ticks: {} elapsed: {:.2}s score: {}",
+        label,
+        simulation.ticks,
+        simulation.elapsed,
+        player_score.get()
+    );
+}
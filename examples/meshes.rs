@@ -12,6 +12,7 @@ use ggez::graphics::{DrawParam, Mesh, Point2};
 use std::f32;
 use std::f32::consts::PI;
 
+use invaders::plugins::rng;
 use invaders::plugins::sprites::*;
 
 const SPACING: f32 = 150.0;
@@ -35,6 +36,7 @@ struct MainState {
     rotation: f32,
     shapes: Vec<Shape>,
     meshes: Vec<Option<Mesh>>,
+    rng: rng::RngStream,
 }
 
 impl MainState {
@@ -57,6 +59,7 @@ impl MainState {
             rotation: 0.0,
             shapes,
             meshes,
+            rng: rng::RngStream::new(rng::random_seed()),
         })
     }
 }
@@ -72,10 +75,12 @@ impl event::EventHandler for MainState {
 
         let mut pos_x = 75.0;
         let mut pos_y = 75.0;
+        let rng = &mut self.rng;
 
         for idx in 0..self.meshes.len() {
             let shape = &self.shapes[idx];
-            let mesh = &self.meshes[idx].get_or_insert_with(|| shape.build_mesh(ctx, 1.0 / 100.0));
+            let mesh =
+                &self.meshes[idx].get_or_insert_with(|| shape.build_mesh(ctx, 1.0 / 100.0, rng));
             graphics::draw_ex(
                 ctx,
                 *mesh,
@@ -0,0 +1,130 @@
+extern crate ggez;
+extern crate invaders;
+extern crate rand;
+extern crate rayon;
+extern crate specs;
+
+use std::env;
+use std::time::{Duration, Instant};
+use rand::{Rng, SeedableRng, XorShiftRng};
+use rayon::ThreadPool;
+use ggez::*;
+use ggez::graphics::Rect;
+use specs::*;
+use invaders::*;
+use invaders::plugins::*;
+
+// Times the systems that spread their work across threads, once on a single thread and once on
+// all of them. Pass the number of entities to try, e.g. `parallel_bench 50000`. Build with
+// --release for real numbers.
+pub fn main() {
+    let count = env::args()
+        .nth(1)
+        .and_then(|arg| arg.parse().ok())
+        .unwrap_or(10_000);
+    if let Err(e) = run(count) {
+        println!("Benchmark failed: {}", e);
+    }
+}
+
+// Game-sized crowding: about 1 entity per 40,000 square units.
+const AREA_PER_ENTITY: f32 = 40_000.0;
+const TICKS: u32 = 50;
+
+fn run(count: usize) -> GameResult<()> {
+    let single_thread = single_thread_pool()?;
+    let mut world = World::new();
+    PluginSet::new()
+        .with(CorePlugin)
+        .with(position_motion::PositionMotionPlugin)
+        .with(simple_physics::SimplePhysicsPlugin)
+        .with(collision::CollisionPlugin)
+        .with(despawn::DespawnPlugin)
+        .build(&mut world)?;
+
+    let mut rng = XorShiftRng::from_seed([1, 2, 3, count as u32]);
+    let side = (count as f32 * AREA_PER_ENTITY).sqrt();
+    // A little smaller than the field, so some things wander out of bounds as they go.
+    let bounds = Rect::new(-side * 0.45, -side * 0.45, side * 0.9, side * 0.9);
+    for _ in 0..count {
+        world
+            .create_entity()
+            .with(position_motion::Position {
+                x: side * rng.gen::<f32>() - side / 2.0,
+                y: side * rng.gen::<f32>() - side / 2.0,
+                ..Default::default()
+            })
+            .with(position_motion::Velocity {
+                x: 400.0 * rng.gen::<f32>() - 200.0,
+                y: 400.0 * rng.gen::<f32>() - 200.0,
+                r: rng.gen::<f32>() - 0.5,
+            })
+            .with(simple_physics::Friction(10.0))
+            .with(simple_physics::SpeedLimit(150.0))
+            .with(collision::Collidable::circle(
+                25.0 + 150.0 * rng.gen::<f32>(),
+            ))
+            .with(despawn::DespawnBounds(bounds))
+            .build();
+    }
+
+    println!(
+        "{} entities, {} threads",
+        count,
+        rayon::current_num_threads()
+    );
+    println!(
+        "{:<20} {:>12} {:>12} {:>8}",
+        "system", "1 thread ms", "parallel ms", "speedup"
+    );
+    let res = &world.res;
+    bench(&single_thread, "motion", || {
+        position_motion::MotionSystem.run_now(res)
+    });
+    bench(&single_thread, "friction", || {
+        simple_physics::FrictionSystem.run_now(res)
+    });
+    bench(&single_thread, "speed_limit", || {
+        simple_physics::SpeedLimitSystem.run_now(res)
+    });
+    bench(&single_thread, "collision", || {
+        collision::SpatialIndexSystem.run_now(res);
+        collision::CollisionSystem.run_now(res);
+    });
+    bench(&single_thread, "despawn_bounds", || {
+        despawn::DespawnBoundsSystem.run_now(res)
+    });
+    Ok(())
+}
+
+// rayon 0.8 only builds pools from a Configuration, which newer rayon-core deprecates.
+#[allow(deprecated)]
+fn single_thread_pool() -> GameResult<ThreadPool> {
+    ThreadPool::new(rayon::Configuration::new().num_threads(1))
+        .map_err(|e| GameError::UnknownError(format!("Could not start a thread pool: {}", e)))
+}
+
+fn bench<F: Fn() + Sync>(single_thread: &ThreadPool, name: &str, run: F) {
+    let single_ms = single_thread.install(|| time(&run));
+    let parallel_ms = time(&run);
+    println!(
+        "{:<20} {:>12.3} {:>12.3} {:>7.2}x",
+        name,
+        single_ms,
+        parallel_ms,
+        single_ms / parallel_ms
+    );
+}
+
+// Average milliseconds per tick.
+fn time<F: Fn()>(run: &F) -> f32 {
+    let start = Instant::now();
+    for _ in 0..TICKS {
+        run();
+    }
+    millis(start.elapsed()) / TICKS as f32
+}
+
+fn millis(duration: Duration) -> f32 {
+    duration.as_secs() as f32 * 1000.0 + duration.subsec_nanos() as f32 * 1e-6
+}
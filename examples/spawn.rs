@@ -49,11 +49,13 @@ impl<'a, 'b> MainState<'a, 'b> {
     fn new(_ctx: &mut Context) -> GameResult<MainState<'a, 'b>> {
         let mut world = World::new();
 
-        let mut dispatcher = DispatcherBuilder::new();
-        let init_funcs = [init, viewport::init, position_motion::init, sprites::init];
-        for init_func in init_funcs.iter() {
-            dispatcher = init_func(&mut world, dispatcher);
-        }
+        let dispatcher = PluginSet::new()
+            .with(CorePlugin)
+            .with(rng::RngPlugin::new(rng::random_seed()))
+            .with(viewport::ViewportPlugin)
+            .with(position_motion::PositionMotionPlugin)
+            .with(sprites::SpritesPlugin)
+            .build(&mut world)?;
 
         for _idx in 0..25 {
             spawn(&mut world);
@@ -67,8 +69,8 @@ impl<'a, 'b> MainState<'a, 'b> {
 }
 
 impl<'a, 'b> event::EventHandler for MainState<'a, 'b> {
-    fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
-        viewport::update(&mut self.world, ctx)?;
+    fn update(&mut self, _ctx: &mut Context) -> GameResult<()> {
+        viewport::update(&mut self.world)?;
 
         self.dispatcher.dispatch(&self.world.res);
 
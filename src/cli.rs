@@ -0,0 +1,124 @@
+use std::path::PathBuf;
+use invaders::game::GameMode;
+
+pub const USAGE: &str = "Usage: invaders [options]
+
+Options:
+    --resources <dir>      Load sounds and fonts from <dir>
+    --width <pixels>       Window width (default 800)
+    --height <pixels>      Window height (default 600)
+    --fullscreen           Fill the screen instead of opening a window
+    --seed <n>             Play the session started with this seed
+    --config <file>        Load gameplay tunables from a TOML file
+    --set <key>=<value>    Override one tunable, e.g. player.thrust=12000 (repeatable)
+    --mode <mode>          Start in attract (default) or playing mode
+    --snapshot <file>      Start from a saved snapshot (F5 saves one while playing)
+    --replay <file>        Play back a recorded session, with the config, mode and
+                           snapshot it was recorded with
+    --record <file>        Save this session's inputs on exit
+    --profile              Time every system and draw phase (F3 shows the numbers)
+    --profile-csv <file>   Save profiler averages and percentiles on exit
+    --profile-trace <file> Save a Chrome trace of every timing on exit
+    --headless             Run the simulation without a window
+    --ticks <n>            With --headless, how many ticks to run (default 7200)
+    --help                 Show this message";
+
+#[derive(Debug)]
+pub struct Options {
+    pub resources: Option<PathBuf>,
+    pub width: u32,
+    pub height: u32,
+    pub fullscreen: bool,
+    pub seed: Option<u64>,
+    pub config: Option<PathBuf>,
+    pub overrides: Vec<String>,
+    pub mode: GameMode,
+    pub snapshot: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub record: Option<PathBuf>,
+    pub profile: bool,
+    pub profile_csv: Option<PathBuf>,
+    pub profile_trace: Option<PathBuf>,
+    pub headless: bool,
+    pub ticks: u64,
+    pub help: bool,
+}
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            resources: None,
+            width: 800,
+            height: 600,
+            fullscreen: false,
+            seed: None,
+            config: None,
+            overrides: Vec::new(),
+            mode: GameMode::Attract,
+            snapshot: None,
+            replay: None,
+            record: None,
+            profile: false,
+            profile_csv: None,
+            profile_trace: None,
+            headless: false,
+            ticks: 7200,
+            help: false,
+        }
+    }
+}
+
+// Parses the arguments after the program name.
+pub fn parse(args: &[String]) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--fullscreen" => options.fullscreen = true,
+            "--headless" => options.headless = true,
+            "--profile" => options.profile = true,
+            "--profile-csv" => options.profile_csv = Some(PathBuf::from(value(arg, args.next())?)),
+            "--profile-trace" => {
+                options.profile_trace = Some(PathBuf::from(value(arg, args.next())?))
+            }
+            "--help" | "-h" => options.help = true,
+            "--resources" => options.resources = Some(PathBuf::from(value(arg, args.next())?)),
+            "--width" => options.width = number(arg, args.next())?,
+            "--height" => options.height = number(arg, args.next())?,
+            "--seed" => options.seed = Some(number(arg, args.next())?),
+            "--config" => options.config = Some(PathBuf::from(value(arg, args.next())?)),
+            "--set" => options.overrides.push(value(arg, args.next())?.to_owned()),
+            "--mode" => options.mode = mode(value(arg, args.next())?)?,
+            "--snapshot" => options.snapshot = Some(PathBuf::from(value(arg, args.next())?)),
+            "--replay" => options.replay = Some(PathBuf::from(value(arg, args.next())?)),
+            "--record" => options.record = Some(PathBuf::from(value(arg, args.next())?)),
+            "--ticks" => options.ticks = number(arg, args.next())?,
+            _ => return Err(format!("Unknown option {}", arg)),
+        }
+    }
+    if options.width == 0 || options.height == 0 {
+        return Err(String::from(
+            "--width and --height must be greater than zero",
+        ));
+    }
+    Ok(options)
+}
+
+fn value<'a>(name: &str, value: Option<&'a String>) -> Result<&'a str, String> {
+    value
+        .map(|value| value.as_str())
+        .ok_or_else(|| format!("{} needs a value", name))
+}
+
+fn number<T: ::std::str::FromStr>(name: &str, arg: Option<&String>) -> Result<T, String> {
+    let raw = value(name, arg)?;
+    raw.parse()
+        .map_err(|_| format!("{} needs a number, got {}", name, raw))
+}
+
+fn mode(name: &str) -> Result<GameMode, String> {
+    match name {
+        "attract" => Ok(GameMode::Attract),
+        "playing" => Ok(GameMode::Playing),
+        _ => Err(format!("--mode must be attract or playing, got {}", name)),
+    }
+}
@@ -0,0 +1,758 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use specs::*;
+use ggez::{GameError, GameResult};
+use toml;
+use plugins::*;
+
+pub struct ConfigPlugin {
+    pub config: GameConfig,
+}
+impl ConfigPlugin {
+    pub fn new(config: GameConfig) -> ConfigPlugin {
+        ConfigPlugin { config }
+    }
+}
+impl Plugin for ConfigPlugin {
+    fn name(&self) -> &'static str {
+        "config"
+    }
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec!["viewport"]
+    }
+    fn setup(&self, world: &mut World) {
+        world
+            .write_resource::<viewport::ViewportState>()
+            .set_playfield(self.config.playfield.width, self.config.playfield.height);
+        world.add_resource(self.config.clone());
+    }
+}
+
+// Gameplay tunables. Any key left out of a config file keeps its default, so a file only needs
+// the values it changes, e.g.
+//
+//     [player]
+//     thrust = 12000.0
+//
+// Overrides given as `key=value`, e.g. `player.thrust=12000`, are applied on top of the file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameConfig {
+    pub playfield: PlayfieldConfig,
+    pub player: PlayerConfig,
+    pub asteroids: AsteroidsConfig,
+    pub collisions: CollisionsConfig,
+    pub power_ups: PowerUpsConfig,
+    pub game_over: GameOverConfig,
+    pub weapons: Vec<guns::WeaponDef>,
+}
+
+impl Default for GameConfig {
+    fn default() -> GameConfig {
+        GameConfig {
+            playfield: PlayfieldConfig::default(),
+            player: PlayerConfig::default(),
+            asteroids: AsteroidsConfig::default(),
+            collisions: CollisionsConfig::default(),
+            power_ups: PowerUpsConfig::default(),
+            game_over: GameOverConfig::default(),
+            weapons: vec![
+                guns::WeaponDef::default(),
+                guns::WeaponDef {
+                    name: String::from("Twin cannon"),
+                    period: 0.35,
+                    burst: 3,
+                    burst_period: 0.06,
+                    heat_per_shot: 12.0,
+                    cooling_per_second: 30.0,
+                    hardpoints: vec![
+                        guns::Hardpoint {
+                            x: -20.0,
+                            y: -40.0,
+                            angle: 0.0,
+                        },
+                        guns::Hardpoint {
+                            x: 20.0,
+                            y: -40.0,
+                            angle: 0.0,
+                        },
+                    ],
+                    ..Default::default()
+                },
+                guns::WeaponDef {
+                    name: String::from("Scattergun"),
+                    period: 0.6,
+                    speed: 650.0,
+                    spread: 7,
+                    spread_angle: 0.1,
+                    ammo: Some(30),
+                    // Pellets don't carry far.
+                    projectile: guns::ProjectileDef {
+                        lifetime: Some(0.8),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            ],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayfieldConfig {
+    pub width: f32,
+    pub height: f32,
+}
+impl Default for PlayfieldConfig {
+    fn default() -> PlayfieldConfig {
+        PlayfieldConfig {
+            width: viewport::PLAYFIELD_WIDTH,
+            height: viewport::PLAYFIELD_HEIGHT,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerConfig {
+    pub regen_per_second: f32,
+    pub thrust: f32,
+    pub lateral_thrust: f32,
+    pub friction: f32,
+    pub speed_limit: f32,
+    // Names of the weapons the player carries, from the weapons list, in the order they're
+    // switched through
+    pub weapons: Vec<String>,
+}
+impl Default for PlayerConfig {
+    fn default() -> PlayerConfig {
+        PlayerConfig {
+            regen_per_second: 15.0,
+            thrust: 10000.0,
+            lateral_thrust: 12500.0,
+            friction: 6000.0,
+            speed_limit: 800.0,
+            weapons: vec![
+                String::from("Blaster"),
+                String::from("Twin cannon"),
+                String::from("Scattergun"),
+            ],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AsteroidsConfig {
+    // Chance of a new asteroid per second at a score of zero
+    pub spawn_chance: f32,
+    // The spawn chance goes up by 1.0 per second for every this many points scored
+    pub spawn_ramp_points: f32,
+    // Points for destroying each size of asteroid
+    pub big_points: i32,
+    pub medium_points: i32,
+    pub small_points: i32,
+    // How fast the pieces of a destroyed asteroid fly apart, on top of its own speed
+    pub fragment_speed: f32,
+}
+impl Default for AsteroidsConfig {
+    fn default() -> AsteroidsConfig {
+        AsteroidsConfig {
+            spawn_chance: 1.5,
+            spawn_ramp_points: 25000.0,
+            big_points: 1000,
+            medium_points: 500,
+            small_points: 250,
+            fragment_speed: 80.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CollisionsConfig {
+    // Dealt to an asteroid by each bullet that hits it
+    pub damage: f32,
+    // How much damage each kind of thing takes from the energy of hitting something
+    pub player_impact: bounce::ImpactDamage,
+    pub planet_impact: bounce::ImpactDamage,
+    pub asteroid_impact: bounce::ImpactDamage,
+}
+impl Default for CollisionsConfig {
+    fn default() -> CollisionsConfig {
+        CollisionsConfig {
+            damage: 100.0,
+            player_impact: bounce::ImpactDamage::default(),
+            planet_impact: bounce::ImpactDamage::default(),
+            // Enough that most knocks break an asteroid, but a glancing one may not.
+            asteroid_impact: bounce::ImpactDamage {
+                scale: 1.0,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PowerUpsConfig {
+    // Chance of a destroyed asteroid dropping a power-up
+    pub drop_chance: f32,
+    // Seconds a power-up lasts once picked up
+    pub duration: f32,
+    // Seconds a dropped power-up drifts around before it's gone
+    pub pickup_lifetime: f32,
+    // Time between shots is multiplied by this during rapid fire
+    pub rapid_fire_period_scale: f32,
+    // Extra bullets fanned out from each shot during spread shot
+    pub spread_extra_shots: usize,
+    pub shield_recharge_per_second: f32,
+    pub planet_repair_per_second: f32,
+    pub score_multiplier: i32,
+}
+impl Default for PowerUpsConfig {
+    fn default() -> PowerUpsConfig {
+        PowerUpsConfig {
+            drop_chance: 0.15,
+            duration: 10.0,
+            pickup_lifetime: 8.0,
+            rapid_fire_period_scale: 0.4,
+            spread_extra_shots: 2,
+            shield_recharge_per_second: 100.0,
+            planet_repair_per_second: 150.0,
+            score_multiplier: 2,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameOverConfig {
+    // Seconds before going back to attract mode
+    pub reset_delay: f32,
+}
+impl Default for GameOverConfig {
+    fn default() -> GameOverConfig {
+        GameOverConfig { reset_delay: 10.0 }
+    }
+}
+
+impl GameConfig {
+    pub fn load<P: AsRef<Path>>(path: P, overrides: &[String]) -> GameResult<GameConfig> {
+        let mut text = String::new();
+        File::open(path)?.read_to_string(&mut text)?;
+        GameConfig::parse(&text, overrides)
+    }
+
+    // The whole config, defaults and all, in the form parse() reads.
+    pub fn to_toml(&self) -> GameResult<String> {
+        toml::to_string(self).map_err(|e| config_error(&e.to_string()))
+    }
+
+    pub fn parse(text: &str, overrides: &[String]) -> GameResult<GameConfig> {
+        let mut value = text
+            .parse::<toml::Value>()
+            .map_err(|e| config_error(&e.to_string()))?;
+        for item in overrides {
+            apply_override(&mut value, item)?;
+        }
+        // Check the keys against the defaults first, since serde's errors don't say where in
+        // the file the problem is.
+        let defaults = toml::Value::try_from(GameConfig::default())
+            .map_err(|e| config_error(&e.to_string()))?;
+        check_keys(&defaults, &value, "")?;
+        let config: GameConfig = value.try_into().map_err(|e| config_error(&e.to_string()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> GameResult<()> {
+        let positive = [
+            ("playfield.width", self.playfield.width),
+            ("playfield.height", self.playfield.height),
+            ("player.thrust", self.player.thrust),
+            ("player.lateral_thrust", self.player.lateral_thrust),
+            ("player.speed_limit", self.player.speed_limit),
+            (
+                "asteroids.spawn_ramp_points",
+                self.asteroids.spawn_ramp_points,
+            ),
+            ("power_ups.duration", self.power_ups.duration),
+            ("power_ups.pickup_lifetime", self.power_ups.pickup_lifetime),
+            (
+                "power_ups.rapid_fire_period_scale",
+                self.power_ups.rapid_fire_period_scale,
+            ),
+            (
+                "collisions.player_impact.exponent",
+                self.collisions.player_impact.exponent,
+            ),
+            (
+                "collisions.planet_impact.exponent",
+                self.collisions.planet_impact.exponent,
+            ),
+            (
+                "collisions.asteroid_impact.exponent",
+                self.collisions.asteroid_impact.exponent,
+            ),
+        ];
+        for (key, value) in positive.iter().cloned() {
+            if !value.is_finite() || value <= 0.0 {
+                return Err(config_error(&format!(
+                    "{} must be greater than zero, got {}",
+                    key, value
+                )));
+            }
+        }
+        let non_negative = [
+            ("player.regen_per_second", self.player.regen_per_second),
+            ("player.friction", self.player.friction),
+            ("asteroids.spawn_chance", self.asteroids.spawn_chance),
+            ("asteroids.fragment_speed", self.asteroids.fragment_speed),
+            ("collisions.damage", self.collisions.damage),
+            (
+                "collisions.player_impact.threshold",
+                self.collisions.player_impact.threshold,
+            ),
+            (
+                "collisions.player_impact.scale",
+                self.collisions.player_impact.scale,
+            ),
+            (
+                "collisions.player_impact.max",
+                self.collisions.player_impact.max,
+            ),
+            (
+                "collisions.planet_impact.threshold",
+                self.collisions.planet_impact.threshold,
+            ),
+            (
+                "collisions.planet_impact.scale",
+                self.collisions.planet_impact.scale,
+            ),
+            (
+                "collisions.planet_impact.max",
+                self.collisions.planet_impact.max,
+            ),
+            (
+                "collisions.asteroid_impact.threshold",
+                self.collisions.asteroid_impact.threshold,
+            ),
+            (
+                "collisions.asteroid_impact.scale",
+                self.collisions.asteroid_impact.scale,
+            ),
+            (
+                "collisions.asteroid_impact.max",
+                self.collisions.asteroid_impact.max,
+            ),
+            ("power_ups.drop_chance", self.power_ups.drop_chance),
+            (
+                "power_ups.shield_recharge_per_second",
+                self.power_ups.shield_recharge_per_second,
+            ),
+            (
+                "power_ups.planet_repair_per_second",
+                self.power_ups.planet_repair_per_second,
+            ),
+            ("game_over.reset_delay", self.game_over.reset_delay),
+        ];
+        for (key, value) in non_negative.iter().cloned() {
+            if !value.is_finite() || value < 0.0 {
+                return Err(config_error(&format!(
+                    "{} must not be negative, got {}",
+                    key, value
+                )));
+            }
+        }
+        if self.power_ups.score_multiplier < 1 {
+            return Err(config_error(
+                "power_ups.score_multiplier must be at least 1",
+            ));
+        }
+        for weapon in &self.weapons {
+            if let Some(e) = weapon_error(weapon) {
+                return Err(config_error(&format!("weapon {}: {}", weapon.name, e)));
+            }
+        }
+        for name in &self.player.weapons {
+            if self.weapon(name).is_none() {
+                return Err(config_error(&format!(
+                    "player.weapons: no weapon called {}",
+                    name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn weapon(&self, name: &str) -> Option<&guns::WeaponDef> {
+        self.weapons.iter().find(|weapon| weapon.name == name)
+    }
+}
+
+fn weapon_error(weapon: &guns::WeaponDef) -> Option<String> {
+    let positive = [
+        ("period", weapon.period),
+        ("speed", weapon.speed),
+        ("max_heat", weapon.max_heat),
+        ("projectile.health", weapon.projectile.health),
+        ("projectile.size", weapon.projectile.size),
+        (
+            "projectile.lifetime",
+            weapon.projectile.lifetime.unwrap_or(1.0),
+        ),
+    ];
+    let non_negative = [
+        ("spread_angle", weapon.spread_angle),
+        ("burst_period", weapon.burst_period),
+        ("heat_per_shot", weapon.heat_per_shot),
+        ("cooling_per_second", weapon.cooling_per_second),
+        ("projectile.bounds_margin", weapon.projectile.bounds_margin),
+    ];
+    for (key, value) in positive.iter().cloned() {
+        if !value.is_finite() || value <= 0.0 {
+            return Some(format!("{} must be greater than zero, got {}", key, value));
+        }
+    }
+    for (key, value) in non_negative.iter().cloned() {
+        if !value.is_finite() || value < 0.0 {
+            return Some(format!("{} must not be negative, got {}", key, value));
+        }
+    }
+    if weapon.spread < 1 {
+        return Some(String::from("spread must be at least 1"));
+    }
+    if weapon.burst < 1 {
+        return Some(String::from("burst must be at least 1"));
+    }
+    if weapon.hardpoints.is_empty() {
+        return Some(String::from("needs at least one hardpoint"));
+    }
+    // Heat only ever goes up without cooling, so the weapon would jam for good.
+    if weapon.heat_per_shot > 0.0 && weapon.cooling_per_second <= 0.0 {
+        return Some(String::from("heat_per_shot needs some cooling_per_second"));
+    }
+    None
+}
+
+// Applies a `section.key=value` override, where value is anything TOML accepts on the right
+// hand side of an `=`.
+fn apply_override(config: &mut toml::Value, item: &str) -> GameResult<()> {
+    let mut parts = item.splitn(2, '=');
+    let key = parts.next().unwrap_or("").trim();
+    let raw = match parts.next() {
+        Some(raw) => raw.trim(),
+        None => return Err(config_error(&format!("override {} needs a =value", key))),
+    };
+    let value = format!("value = {}", raw)
+        .parse::<toml::Value>()
+        .ok()
+        .and_then(|parsed| parsed.get("value").cloned())
+        .ok_or_else(|| config_error(&format!("bad value for {}: {}", key, raw)))?;
+
+    let mut table = config;
+    let path: Vec<&str> = key.split('.').collect();
+    let (last, sections) = path.split_last().unwrap();
+    for section in sections {
+        let current = table;
+        table = match *current {
+            toml::Value::Table(ref mut entries) => entries
+                .entry(section.to_string())
+                .or_insert_with(|| toml::Value::Table(toml::value::Table::new())),
+            _ => return Err(config_error(&format!("{} is not a section", key))),
+        };
+    }
+    match *table {
+        toml::Value::Table(ref mut entries) => {
+            entries.insert(last.to_string(), value);
+            Ok(())
+        }
+        _ => Err(config_error(&format!("{} is not a section", key))),
+    }
+}
+
+fn check_keys(defaults: &toml::Value, given: &toml::Value, prefix: &str) -> GameResult<()> {
+    match *defaults {
+        toml::Value::Table(ref defaults) => {
+            let given = match *given {
+                toml::Value::Table(ref given) => given,
+                _ => return Err(config_error(&format!("{} must be a section", prefix))),
+            };
+            for (key, value) in given {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                match defaults.get(key) {
+                    Some(default) => check_keys(default, value, &path)?,
+                    None => return Err(config_error(&format!("unknown key {}", path))),
+                }
+            }
+            Ok(())
+        }
+        toml::Value::Array(ref defaults) => {
+            let given = match *given {
+                toml::Value::Array(ref given) => given,
+                _ => return Err(config_error(&format!("{} must be a list", prefix))),
+            };
+            let template = match array_template(defaults) {
+                Some(template) => template,
+                None => return Ok(()),
+            };
+            for (idx, value) in given.iter().enumerate() {
+                check_keys(&template, value, &format!("{}[{}]", prefix, idx))?;
+            }
+            Ok(())
+        }
+        toml::Value::Float(_) => match *given {
+            toml::Value::Float(_) | toml::Value::Integer(_) => Ok(()),
+            _ => Err(config_error(&format!("{} must be a number", prefix))),
+        },
+        toml::Value::Integer(_) => match *given {
+            toml::Value::Integer(_) => Ok(()),
+            _ => Err(config_error(&format!("{} must be a whole number", prefix))),
+        },
+        toml::Value::String(_) => match *given {
+            toml::Value::String(_) => Ok(()),
+            _ => Err(config_error(&format!("{} must be a string", prefix))),
+        },
+        toml::Value::Boolean(_) => match *given {
+            toml::Value::Boolean(_) => Ok(()),
+            _ => Err(config_error(&format!("{} must be true or false", prefix))),
+        },
+        _ => Ok(()),
+    }
+}
+
+// What every element of a list should look like, going by the defaults. Lists of sections are
+// merged into one, all the way down, since a key left out of one default (e.g. an unset option)
+// may be in another.
+fn array_template(defaults: &[toml::Value]) -> Option<toml::Value> {
+    let first = defaults.first()?;
+    match *first {
+        toml::Value::Table(_) => {
+            let mut merged = toml::value::Table::new();
+            for default in defaults {
+                if let toml::Value::Table(ref entries) = *default {
+                    merge_tables(&mut merged, entries);
+                }
+            }
+            Some(toml::Value::Table(merged))
+        }
+        _ => Some(first.clone()),
+    }
+}
+
+fn merge_tables(merged: &mut toml::value::Table, entries: &toml::value::Table) {
+    for (key, value) in entries {
+        if let toml::Value::Table(ref more) = *value {
+            if let Some(&mut toml::Value::Table(ref mut existing)) = merged.get_mut(key) {
+                merge_tables(existing, more);
+                continue;
+            }
+        }
+        merged.entry(key.clone()).or_insert_with(|| value.clone());
+    }
+}
+
+fn config_error(message: &str) -> GameError {
+    GameError::ConfigError(format!("Bad game config: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(text: &str, overrides: &[&str]) -> String {
+        let overrides: Vec<String> = overrides.iter().map(|item| item.to_string()).collect();
+        match GameConfig::parse(text, &overrides) {
+            Ok(_) => panic!("expected {:?} with {:?} not to parse", text, overrides),
+            Err(GameError::ConfigError(message)) => message,
+            Err(e) => panic!("expected a config error, got {:?}", e),
+        }
+    }
+
+    fn assert_error(text: &str, overrides: &[&str], expected: &str) {
+        let message = error(text, overrides);
+        assert!(
+            message.contains(expected),
+            "expected an error about {:?}, got {:?}",
+            expected,
+            message
+        );
+    }
+
+    #[test]
+    fn partial_files_keep_defaults() {
+        let config = GameConfig::parse("[player]\nthrust = 12000.0\n", &[]).unwrap();
+        let mut expected = GameConfig::default();
+        expected.player.thrust = 12000.0;
+        assert_eq!(config, expected);
+        assert_eq!(GameConfig::parse("", &[]).unwrap(), GameConfig::default());
+    }
+
+    #[test]
+    fn round_trips_through_toml() {
+        let mut config = GameConfig::default();
+        config.asteroids.big_points = 2000;
+        let text = config.to_toml().unwrap();
+        assert_eq!(GameConfig::parse(&text, &[]).unwrap(), config);
+    }
+
+    #[test]
+    fn unknown_keys() {
+        assert_error("speed = 1.0\n", &[], "unknown key speed");
+        assert_error(
+            "[player]\nthrusty = 1.0\n",
+            &[],
+            "unknown key player.thrusty",
+        );
+        assert_error("[nope]\n", &[], "unknown key nope");
+    }
+
+    #[test]
+    fn type_mismatches() {
+        // Whole numbers are fine where a number is expected.
+        let config = GameConfig::parse("[player]\nthrust = 12000\n", &[]).unwrap();
+        assert_eq!(config.player.thrust, 12000.0);
+
+        assert_error(
+            "[player]\nthrust = \"fast\"\n",
+            &[],
+            "player.thrust must be a number",
+        );
+        assert_error(
+            "[asteroids]\nbig_points = 1.5\n",
+            &[],
+            "asteroids.big_points must be a whole number",
+        );
+        assert_error("player = 1\n", &[], "player must be a section");
+        assert_error(
+            "[player]\nweapons = \"Blaster\"\n",
+            &[],
+            "player.weapons must be a list",
+        );
+        assert_error(
+            "[player]\nweapons = [1]\n",
+            &[],
+            "player.weapons[0] must be a string",
+        );
+    }
+
+    #[test]
+    fn nested_list_sections() {
+        // Only some default weapons set a projectile lifetime, but any weapon may.
+        let text = "[player]\nweapons = [\"Pea shooter\"]\n\n\
+                    [[weapons]]\nname = \"Pea shooter\"\n\n\
+                    [weapons.projectile]\nlifetime = 0.5\n\n\
+                    [[weapons.hardpoints]]\ny = -30.0\n";
+        let config = GameConfig::parse(text, &[]).unwrap();
+        assert_eq!(config.weapons.len(), 1);
+        assert_eq!(config.weapons[0].projectile.lifetime, Some(0.5));
+        assert_eq!(config.weapons[0].hardpoints[0].y, -30.0);
+
+        assert_error(
+            &text.replace("lifetime", "lifespan"),
+            &[],
+            "unknown key weapons[0].projectile.lifespan",
+        );
+        assert_error(
+            &text.replace("y = -30.0", "z = -30.0"),
+            &[],
+            "unknown key weapons[0].hardpoints[0].z",
+        );
+        assert_error(
+            &text.replace("lifetime = 0.5", "fast = \"yes\""),
+            &[],
+            "weapons[0].projectile.fast must be true or false",
+        );
+    }
+
+    #[test]
+    fn overrides() {
+        let config = GameConfig::parse(
+            "[player]\nthrust = 12000.0\n",
+            &[
+                String::from("player.thrust=14000"),
+                String::from(" asteroids.big_points = 5 "),
+            ],
+        )
+        .unwrap();
+        assert_eq!(config.player.thrust, 14000.0);
+        assert_eq!(config.asteroids.big_points, 5);
+
+        assert_error(
+            "",
+            &["player.thrust"],
+            "override player.thrust needs a =value",
+        );
+        assert_error("", &["player.thrust=fast"], "bad value for player.thrust");
+        assert_error("", &["player.thrusty=1.0"], "unknown key player.thrusty");
+        assert_error(
+            "",
+            &["player.thrust.x=1.0"],
+            "player.thrust must be a number",
+        );
+        assert_error(
+            "[player]\nthrust = 12000.0\n",
+            &["player.thrust.x=1.0"],
+            "player.thrust.x is not a section",
+        );
+        assert_error(
+            "",
+            &["player.thrust=-1.0"],
+            "player.thrust must be greater than zero",
+        );
+    }
+
+    #[test]
+    fn validation() {
+        assert_error(
+            "[playfield]\nwidth = 0.0\n",
+            &[],
+            "playfield.width must be greater than zero",
+        );
+        assert_error(
+            "[asteroids]\nspawn_chance = -1.0\n",
+            &[],
+            "asteroids.spawn_chance must not be negative",
+        );
+        assert_error(
+            "[power_ups]\nscore_multiplier = 0\n",
+            &[],
+            "power_ups.score_multiplier must be at least 1",
+        );
+        assert_error(
+            "[player]\nweapons = [\"Blaster\", \"Laser\"]\n",
+            &[],
+            "player.weapons: no weapon called Laser",
+        );
+
+        let weapon = "[player]\nweapons = [\"Pea shooter\"]\n\n\
+                      [[weapons]]\nname = \"Pea shooter\"\n";
+        assert_error(
+            &format!("{}burst = 0\n", weapon),
+            &[],
+            "weapon Pea shooter: burst must be at least 1",
+        );
+        assert_error(
+            &format!("{}heat_per_shot = 5.0\n", weapon),
+            &[],
+            "weapon Pea shooter: heat_per_shot needs some cooling_per_second",
+        );
+        assert_error(
+            &format!("{}hardpoints = []\n", weapon),
+            &[],
+            "weapon Pea shooter: needs at least one hardpoint",
+        );
+        assert_error(
+            &format!("{}[weapons.projectile]\nlifetime = 0.0\n", weapon),
+            &[],
+            "weapon Pea shooter: projectile.lifetime must be greater than zero",
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use std::cmp::Reverse;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use specs::*;
+use ggez::*;
+use plugins::*;
+
+pub const TABLE_SIZE: usize = 10;
+pub const FILENAME: &str = "high_scores.txt";
+
+pub struct HighScoresPlugin;
+impl Plugin for HighScoresPlugin {
+    fn name(&self) -> &'static str {
+        "high_scores"
+    }
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec!["score"]
+    }
+    fn setup(&self, world: &mut World) {
+        world.add_resource(HighScoreTable::new());
+    }
+}
+
+// Load the table from `path` and save back to it whenever a score is added. Without this the
+// table only lasts as long as the world does.
+pub fn load<P: AsRef<Path>>(world: &mut World, path: P) -> GameResult<()> {
+    let mut table = world.write_resource::<HighScoreTable>();
+    table.path = Some(path.as_ref().to_path_buf());
+    if path.as_ref().exists() {
+        let mut data = String::new();
+        File::open(path)?.read_to_string(&mut data)?;
+        table.entries = HighScoreTable::decode(&data)?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HighScore {
+    pub initials: String,
+    pub score: i32,
+    // YYYY-MM-DD
+    pub date: String,
+    pub seed: u64,
+}
+
+#[derive(Debug)]
+pub struct HighScoreTable {
+    pub entries: Vec<HighScore>,
+    path: Option<PathBuf>,
+}
+impl HighScoreTable {
+    pub fn new() -> HighScoreTable {
+        HighScoreTable {
+            entries: Vec::new(),
+            path: None,
+        }
+    }
+
+    pub fn qualifies(&self, score: i32) -> bool {
+        score > 0
+            && (self.entries.len() < TABLE_SIZE
+                || self.entries.iter().any(|entry| score > entry.score))
+    }
+
+    // Ties go below the scores already in the table - they got there first.
+    pub fn add(&mut self, entry: HighScore) -> GameResult<()> {
+        let idx = self
+            .entries
+            .iter()
+            .position(|existing| entry.score > existing.score)
+            .unwrap_or(self.entries.len());
+        self.entries.insert(idx, entry);
+        self.entries.truncate(TABLE_SIZE);
+        self.save()
+    }
+
+    fn save(&self) -> GameResult<()> {
+        if let Some(ref path) = self.path {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            File::create(path)?.write_all(self.encode().as_bytes())?;
+        }
+        Ok(())
+    }
+
+    // One entry per line: score, initials, date, seed.
+    pub fn encode(&self) -> String {
+        let mut out = String::new();
+        for entry in &self.entries {
+            out.push_str(&format!(
+                "{} {} {} {}\n",
+                entry.score, entry.initials, entry.date, entry.seed
+            ));
+        }
+        out
+    }
+
+    pub fn decode(data: &str) -> GameResult<Vec<HighScore>> {
+        let mut entries = Vec::new();
+        for line in data.lines().filter(|line| !line.trim().is_empty()) {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() != 4 {
+                return Err(high_score_error(line));
+            }
+            entries.push(HighScore {
+                score: fields[0].parse().map_err(|_| high_score_error(line))?,
+                initials: String::from(fields[1]),
+                date: String::from(fields[2]),
+                seed: fields[3].parse().map_err(|_| high_score_error(line))?,
+            });
+        }
+        entries.sort_by_key(|entry| Reverse(entry.score));
+        entries.truncate(TABLE_SIZE);
+        Ok(entries)
+    }
+}
+impl Default for HighScoreTable {
+    fn default() -> HighScoreTable {
+        HighScoreTable::new()
+    }
+}
+
+fn high_score_error(line: &str) -> GameError {
+    GameError::ResourceLoadError(format!("Bad high score entry: {}", line))
+}
+
+// Today's date in UTC, as YYYY-MM-DD.
+pub fn today() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// Days since 1970-01-01 to a proleptic Gregorian date, after Howard Hinnant's algorithm.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = (if z >= 0 { z } else { z - 146_096 }) / 146_097;
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+pub fn draw(world: &mut World, font: &mut fonts::Font, ctx: &mut Context) -> GameResult<()> {
+    let table = world.read_resource::<HighScoreTable>();
+    if table.entries.is_empty() {
+        return Ok(());
+    }
+    let mut text = String::from("High Scores\n\n");
+    for (idx, entry) in table.entries.iter().enumerate() {
+        text.push_str(&format!(
+            "{:2}. {:3} {:07}  {}\n",
+            idx + 1,
+            entry.initials,
+            entry.score,
+            entry.date
+        ));
+    }
+    font.draw(
+        ctx,
+        &text,
+        fonts::DrawOptions {
+            x: -400.0,
+            y: 50.0,
+            scale: 1.5,
+            ..Default::default()
+        },
+    )?;
+
+    Ok(())
+}
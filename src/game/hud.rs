@@ -3,21 +3,10 @@ use ggez::*;
 use ggez::graphics::*;
 use plugins::*;
 
-use super::{GameMode, GameModeManager, HeroPlanet, HeroPlayer};
-
-pub fn init<'a, 'b>(
-    _world: &mut World,
-    dispatcher: DispatcherBuilder<'a, 'b>,
-) -> DispatcherBuilder<'a, 'b> {
-    dispatcher
-}
+use super::{HeroPlanet, HeroPlayer};
+use super::power_ups::ActivePowerUps;
 
 pub fn draw(world: &mut World, font: &mut fonts::Font, ctx: &mut Context) -> GameResult<()> {
-    let game_mode = world.read_resource::<GameModeManager>();
-    if !game_mode.is_current(GameMode::Playing) {
-        return Ok(());
-    }
-
     let viewport_state = world.read_resource::<viewport::ViewportState>();
 
     let player_score = world.read_resource::<score::PlayerScore>();
@@ -36,8 +25,9 @@ pub fn draw(world: &mut World, font: &mut fonts::Font, ctx: &mut Context) -> Gam
     let scale = 50.0;
     let base_x = viewport_state.screen.x + scale * 1.5;
     let base_y = viewport_state.screen.y + scale * 1.5;
-    let planet_icon = sprites::Shape::PlanetIcon.build_mesh(ctx, 1.0 / scale);
-    let player_icon = sprites::Shape::Player.build_mesh(ctx, 1.0 / scale);
+    let mut rng = world.write_resource::<rng::GameRng>();
+    let planet_icon = sprites::Shape::PlanetIcon.build_mesh(ctx, 1.0 / scale, &mut rng.cosmetic);
+    let player_icon = sprites::Shape::Player.build_mesh(ctx, 1.0 / scale, &mut rng.cosmetic);
 
     for (health, _planet) in (
         &world.read::<health_damage::Health>(),
@@ -71,6 +61,43 @@ pub fn draw(world: &mut World, font: &mut fonts::Font, ctx: &mut Context) -> Gam
         )?;
     }
 
+    // The weapon in hand under the gauges, then the power-ups the player has with how long each
+    // has left.
+    for (gun, _player) in (&world.read::<guns::Gun>(), &world.read::<HeroPlayer>()).join() {
+        if let Some(weapon) = gun.weapon() {
+            let mut label = weapon.def.name.clone();
+            if let Some(ammo) = weapon.ammo {
+                label.push_str(&format!(" {}", ammo));
+            }
+            if weapon.overheated {
+                label.push_str(" overheated");
+            }
+            font.draw(
+                ctx,
+                &label,
+                fonts::DrawOptions {
+                    x: base_x - (scale * 0.5),
+                    y: base_y + (scale * 3.0),
+                    scale: 1.5,
+                    ..Default::default()
+                },
+            )?;
+        }
+    }
+    let active_power_ups = world.read_resource::<ActivePowerUps>();
+    for (idx, (power_up, remaining)) in active_power_ups.0.iter().enumerate() {
+        font.draw(
+            ctx,
+            &format!("{} {:.1}", power_up.name(), remaining),
+            fonts::DrawOptions {
+                x: base_x - (scale * 0.5),
+                y: base_y + (scale * 3.75) + (scale * 0.75 * idx as f32),
+                scale: 1.5,
+                ..Default::default()
+            },
+        )?;
+    }
+
     Ok(())
 }
 
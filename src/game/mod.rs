@@ -1,29 +1,41 @@
 use specs::*;
 use ggez::*;
 use plugins::*;
+use config::AsteroidsConfig;
 
+pub mod high_scores;
 pub mod hud;
 pub mod mode_attract;
+pub mod mode_enter_initials;
 pub mod mode_game_over;
+pub mod mode_paused;
 pub mod mode_playing;
+pub mod modes;
+pub mod power_ups;
 pub mod prefabs;
+pub mod snapshot;
 pub mod sound_effects;
 
-pub fn init<'a, 'b>(
-    world: &mut World,
-    dispatcher: DispatcherBuilder<'a, 'b>,
-) -> DispatcherBuilder<'a, 'b> {
-    world.add_resource(GameModeManager::new());
-
-    world.register::<HeroPlanet>();
-    world.register::<HeroPlayer>();
-
-    let dispatcher = hud::init(world, dispatcher);
-    let dispatcher = sound_effects::init(world, dispatcher);
-    let dispatcher = mode_attract::init(world, dispatcher);
-    let dispatcher = mode_playing::init(world, dispatcher);
-    let dispatcher = mode_game_over::init(world, dispatcher);
-    dispatcher
+pub struct GamePlugin;
+impl Plugin for GamePlugin {
+    fn name(&self) -> &'static str {
+        "game"
+    }
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec!["player_control"]
+    }
+    fn setup(&self, world: &mut World) {
+        world.add_resource(GameModeManager::new());
+        world.add_resource(modes::ModeMachine::new());
+        world.add_resource(modes::ModeEvents::new());
+        world.register::<HeroPlanet>();
+        world.register::<HeroPlayer>();
+        world.register::<Asteroid>();
+    }
+    fn update(&self, world: &mut World) -> GameResult<()> {
+        modes::update(world);
+        Ok(())
+    }
 }
 
 pub fn draw(
@@ -32,21 +44,15 @@ pub fn draw(
     font: &mut fonts::Font,
     sound_effects: &mut sound_effects::SoundEffects,
 ) -> GameResult<()> {
-    mode_attract::draw(world, font, ctx)?;
-    mode_playing::draw(world, font, ctx)?;
-    mode_game_over::draw(world, font, ctx)?;
-    hud::draw(world, font, ctx)?;
+    modes::draw(world, font, ctx)?;
     sound_effects::play(world, ctx, sound_effects)?;
     Ok(())
 }
 
-pub fn reset_game(
-    entities: &Entities,
-    inputs: &mut FetchMut<player_control::Inputs>,
-    delete_entities: bool,
-) {
-    inputs.reset();
+pub fn reset_game(world: &mut World, delete_entities: bool) {
+    world.write_resource::<player_control::Inputs>().reset();
     if delete_entities {
+        let entities = world.entities();
         for entity in entities.join() {
             if let Err(e) = entities.delete(entity) {
                 println!("Error deleting entity: {:?}", e);
@@ -61,38 +67,106 @@ pub struct HeroPlayer;
 #[derive(Component, Debug)]
 pub struct HeroPlanet;
 
-#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+#[derive(Component, Debug)]
+pub struct Asteroid(pub AsteroidSize);
+
+// Shooting an asteroid breaks it into smaller ones, until they're too small to break.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AsteroidSize {
+    Big,
+    Medium,
+    Small,
+}
+impl AsteroidSize {
+    // Smallest and largest diameter.
+    pub fn diameters(self) -> (f32, f32) {
+        match self {
+            AsteroidSize::Big => (110.0, 175.0),
+            AsteroidSize::Medium => (60.0, 100.0),
+            AsteroidSize::Small => (25.0, 50.0),
+        }
+    }
+
+    // What this breaks into, and how many.
+    pub fn fragments(self) -> Option<(AsteroidSize, usize)> {
+        match self {
+            AsteroidSize::Big => Some((AsteroidSize::Medium, 2)),
+            AsteroidSize::Medium => Some((AsteroidSize::Small, 2)),
+            AsteroidSize::Small => None,
+        }
+    }
+
+    pub fn points(self, config: &AsteroidsConfig) -> i32 {
+        match self {
+            AsteroidSize::Big => config.big_points,
+            AsteroidSize::Medium => config.medium_points,
+            AsteroidSize::Small => config.small_points,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum GameMode {
     Attract,
     Playing,
+    Paused,
     GameOver,
+    EnterInitials,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ModeRequest {
+    // Exit every mode on the stack, then enter this one.
+    Change(GameMode),
+    // Enter this mode on top of the current one, which stays put underneath.
+    Push(GameMode),
+    // Exit the top mode, going back to whatever was underneath.
+    Pop,
 }
 
+// The stack of active modes, top last. Modes and systems ask for transitions here, and the mode
+// machine carries them out at the end of the tick.
 pub struct GameModeManager {
-    pub current_mode: GameMode,
-    pub pending_mode: GameMode,
-    pub resolved: bool,
+    stack: Vec<GameMode>,
+    requests: Vec<ModeRequest>,
 }
 impl GameModeManager {
     pub fn new() -> GameModeManager {
         GameModeManager {
-            current_mode: GameMode::Attract,
-            pending_mode: GameMode::Attract,
-            resolved: false,
+            stack: Vec::new(),
+            requests: vec![ModeRequest::Change(GameMode::Attract)],
         }
     }
     pub fn change(&mut self, mode: GameMode) {
-        self.pending_mode = mode;
-        self.resolved = false;
+        self.requests.push(ModeRequest::Change(mode));
+    }
+    pub fn push(&mut self, mode: GameMode) {
+        self.requests.push(ModeRequest::Push(mode));
     }
-    pub fn resolve(&mut self) {
-        self.current_mode = self.pending_mode;
-        self.resolved = true;
+    pub fn pop(&mut self) {
+        self.requests.push(ModeRequest::Pop);
     }
-    pub fn is_pending(&self, mode: GameMode) -> bool {
-        self.pending_mode == mode && !self.resolved
+    // Put back a saved stack as-is, without entering or exiting anything.
+    fn restore(&mut self, stack: Vec<GameMode>) {
+        self.stack = stack;
+        self.requests.clear();
+    }
+    pub fn current(&self) -> Option<GameMode> {
+        self.stack.last().cloned()
     }
     pub fn is_current(&self, mode: GameMode) -> bool {
-        self.current_mode == mode && self.resolved
+        self.current() == Some(mode)
+    }
+    // True if `mode` is anywhere on the stack, e.g. Playing underneath the pause menu.
+    pub fn is_active(&self, mode: GameMode) -> bool {
+        self.stack.contains(&mode)
+    }
+    fn take_requests(&mut self) -> Vec<ModeRequest> {
+        self.requests.drain(..).collect()
+    }
+}
+impl Default for GameModeManager {
+    fn default() -> GameModeManager {
+        GameModeManager::new()
     }
 }
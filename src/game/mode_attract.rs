@@ -1,43 +1,79 @@
-use rand;
+use rand::Rng;
 
 use specs::*;
 use ggez::*;
 use plugins::*;
-use super::{prefabs, reset_game, GameMode, GameModeManager};
+use config::GameConfig;
+use DeltaTime;
+use super::{high_scores, prefabs, reset_game, GameMode, GameModeManager};
+use super::modes::{self, Mode};
 
-pub fn init<'a, 'b>(
-    _world: &mut World,
-    dispatcher: DispatcherBuilder<'a, 'b>,
-) -> DispatcherBuilder<'a, 'b> {
-    dispatcher.add(AttractModeSystem, "attract_mode", &[])
+pub struct AttractModePlugin;
+impl Plugin for AttractModePlugin {
+    fn name(&self) -> &'static str {
+        "mode_attract"
+    }
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec![
+            "game",
+            "config",
+            "rng",
+            "player_control",
+            "position_motion",
+            "collision",
+            "health_damage",
+            "despawn",
+            "score",
+            "high_scores",
+        ]
+    }
+    fn setup(&self, world: &mut World) {
+        modes::register(world, GameMode::Attract, AttractMode);
+    }
+}
+
+pub struct AttractMode;
+impl Mode for AttractMode {
+    fn on_enter(&mut self, world: &mut World) {
+        reset_game(world, true);
+        let entities = world.entities();
+        let lazy = world.read_resource::<LazyUpdate>();
+        let config = world.read_resource::<GameConfig>();
+        prefabs::planet(&config, entities.create(), &lazy);
+    }
+    fn update(&mut self, world: &mut World) {
+        AttractModeSystem.run_now(&world.res);
+    }
+    fn draw(
+        &mut self,
+        world: &mut World,
+        font: &mut fonts::Font,
+        ctx: &mut Context,
+    ) -> GameResult<()> {
+        draw(world, font, ctx)
+    }
 }
 
+// Chance of a new asteroid per second in the background of the title screen.
+const ASTEROID_SPAWN_CHANCE: f32 = 6.0;
+
 pub struct AttractModeSystem;
 impl<'a> System<'a> for AttractModeSystem {
     type SystemData = (
         Entities<'a>,
+        Fetch<'a, DeltaTime>,
         Fetch<'a, LazyUpdate>,
         FetchMut<'a, GameModeManager>,
-        FetchMut<'a, player_control::Inputs>,
-        ReadStorage<'a, position_motion::Position>,
-        ReadStorage<'a, collision::Collidable>,
+        Fetch<'a, player_control::Inputs>,
+        Fetch<'a, collision::SpatialIndex>,
+        FetchMut<'a, rng::GameRng>,
+        Fetch<'a, GameConfig>,
     );
     fn run(&mut self, data: Self::SystemData) {
-        let (entities, lazy, mut game_mode, mut inputs, positions, collidables) = data;
-
-        if game_mode.is_pending(GameMode::Attract) {
-            reset_game(&entities, &mut inputs, true);
-            prefabs::planet(entities.create(), &lazy);
-            game_mode.resolve();
-            return;
-        }
+        let (entities, delta, lazy, mut game_mode, inputs, index, mut rng, config) = data;
 
-        if !game_mode.is_current(GameMode::Attract) {
-            return;
-        }
-
-        if rand::random::<f32>() < 0.1 {
-            prefabs::asteroid(&positions, &collidables, entities.create(), &lazy);
+        if rng.gameplay.gen::<f32>() < ASTEROID_SPAWN_CHANCE * delta.0 {
+            prefabs::asteroid(&mut rng.gameplay, &config, &index, entities.create(), &lazy);
         }
 
         if inputs.fire {
@@ -47,11 +83,6 @@ impl<'a> System<'a> for AttractModeSystem {
 }
 
 pub fn draw(world: &mut World, font: &mut fonts::Font, ctx: &mut Context) -> GameResult<()> {
-    let game_mode = world.read_resource::<GameModeManager>();
-    if !game_mode.is_current(GameMode::Attract) {
-        return Ok(());
-    }
-
     // let viewport_state = world.read_resource::<viewport::ViewportState>();
     font.draw(
         ctx,
@@ -63,6 +94,7 @@ pub fn draw(world: &mut World, font: &mut fonts::Font, ctx: &mut Context) -> Gam
             ..Default::default()
         },
     )?;
+    high_scores::draw(world, font, ctx)?;
 
     Ok(())
 }
@@ -0,0 +1,171 @@
+use specs::*;
+use ggez::*;
+use plugins::*;
+use game::*;
+
+use super::{GameMode, GameModeManager};
+use super::modes::{self, Mode};
+use game::sound_effects::SoundEffectType;
+
+const INITIALS_LEN: usize = 3;
+const LETTERS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+pub struct EnterInitialsModePlugin;
+impl Plugin for EnterInitialsModePlugin {
+    fn name(&self) -> &'static str {
+        "mode_enter_initials"
+    }
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec![
+            "game",
+            "rng",
+            "player_control",
+            "score",
+            "high_scores",
+            "sound_effects",
+        ]
+    }
+    fn setup(&self, world: &mut World) {
+        world.add_resource(EnterInitialsModeState::new());
+        modes::register(world, GameMode::EnterInitials, EnterInitialsMode);
+    }
+}
+
+// Follows GameOver when the final score makes the high score table.
+pub struct EnterInitialsMode;
+impl Mode for EnterInitialsMode {
+    fn on_enter(&mut self, world: &mut World) {
+        world.write_resource::<player_control::Inputs>().reset();
+        world.write_resource::<EnterInitialsModeState>().reset();
+    }
+    fn on_exit(&mut self, world: &mut World) {
+        world.write_resource::<player_control::Inputs>().reset();
+    }
+    fn update(&mut self, world: &mut World) {
+        EnterInitialsModeSystem.run_now(&world.res);
+    }
+    fn draw(
+        &mut self,
+        world: &mut World,
+        font: &mut fonts::Font,
+        ctx: &mut Context,
+    ) -> GameResult<()> {
+        draw(world, font, ctx)
+    }
+}
+
+pub struct EnterInitialsModeState {
+    // Index into LETTERS for each initial.
+    letters: [usize; INITIALS_LEN],
+    cursor: usize,
+}
+impl EnterInitialsModeState {
+    pub fn new() -> EnterInitialsModeState {
+        EnterInitialsModeState {
+            letters: [0; INITIALS_LEN],
+            cursor: 0,
+        }
+    }
+    pub fn reset(&mut self) {
+        *self = EnterInitialsModeState::new();
+    }
+    pub fn initials(&self) -> String {
+        self.letters
+            .iter()
+            .map(|&idx| LETTERS[idx] as char)
+            .collect()
+    }
+    fn previous_letter(&mut self) {
+        let letter = &mut self.letters[self.cursor];
+        *letter = (*letter + LETTERS.len() - 1) % LETTERS.len();
+    }
+    fn next_letter(&mut self) {
+        let letter = &mut self.letters[self.cursor];
+        *letter = (*letter + 1) % LETTERS.len();
+    }
+}
+impl Default for EnterInitialsModeState {
+    fn default() -> EnterInitialsModeState {
+        EnterInitialsModeState::new()
+    }
+}
+
+pub struct EnterInitialsModeSystem;
+impl<'a> System<'a> for EnterInitialsModeSystem {
+    type SystemData = (
+        FetchMut<'a, GameModeManager>,
+        FetchMut<'a, EnterInitialsModeState>,
+        FetchMut<'a, high_scores::HighScoreTable>,
+        FetchMut<'a, sound_effects::SoundEffectQueue>,
+        FetchMut<'a, player_control::Inputs>,
+        Fetch<'a, score::PlayerScore>,
+        Fetch<'a, rng::GameRng>,
+    );
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut game_mode, mut state, mut table, mut sounds, mut inputs, score, rng) = data;
+
+        // Each press moves one step, so use up each input as it's handled.
+        if inputs.up {
+            inputs.up = false;
+            state.next_letter();
+        }
+        if inputs.down {
+            inputs.down = false;
+            state.previous_letter();
+        }
+        if inputs.left {
+            inputs.left = false;
+            if state.cursor > 0 {
+                state.cursor -= 1;
+            }
+        }
+        if inputs.fire || inputs.right {
+            inputs.fire = false;
+            inputs.right = false;
+            state.cursor += 1;
+            if state.cursor == INITIALS_LEN {
+                let entry = high_scores::HighScore {
+                    initials: state.initials(),
+                    score: score.get(),
+                    date: high_scores::today(),
+                    seed: rng.seed,
+                };
+                if let Err(e) = table.add(entry) {
+                    println!("Error saving high scores: {}", e);
+                }
+                sounds.play(SoundEffectType::Ready);
+                game_mode.change(GameMode::Attract);
+            }
+        }
+    }
+}
+
+pub fn draw(world: &mut World, font: &mut fonts::Font, ctx: &mut Context) -> GameResult<()> {
+    let state = world.read_resource::<EnterInitialsModeState>();
+    let player_score = world.read_resource::<score::PlayerScore>();
+    let initials = state.initials();
+    let mut entry = String::new();
+    for (idx, letter) in initials.chars().enumerate() {
+        if idx == state.cursor {
+            entry.push_str(&format!("[{}]", letter));
+        } else {
+            entry.push_str(&format!(" {} ", letter));
+        }
+    }
+    font.draw(
+        ctx,
+        &format!(
+            "New High Score!\n\n {:07}\n\nEnter your initials:\n\n  {}",
+            player_score.get(),
+            entry
+        ),
+        fonts::DrawOptions {
+            x: -300.0,
+            y: -250.0,
+            scale: 3.0,
+            ..Default::default()
+        },
+    )?;
+
+    Ok(())
+}
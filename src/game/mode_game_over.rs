@@ -3,30 +3,98 @@ use ggez::*;
 use plugins::*;
 use game::*;
 use DeltaTime;
+use config::GameConfig;
 use super::{reset_game, GameMode, GameModeManager, HeroPlayer};
+use super::modes::{self, Mode};
 use game::sound_effects::SoundEffectType;
 
-const RESET_TTL_MAX: f32 = 10.0;
+pub struct GameOverModePlugin;
+impl Plugin for GameOverModePlugin {
+    fn name(&self) -> &'static str {
+        "mode_game_over"
+    }
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec![
+            "game",
+            "config",
+            "rng",
+            "player_control",
+            "position_motion",
+            "collision",
+            "health_damage",
+            "despawn",
+            "score",
+            "high_scores",
+            "sound_effects",
+        ]
+    }
+    fn setup(&self, world: &mut World) {
+        world.add_resource(GameOverModeState::new());
+        modes::register(world, GameMode::GameOver, GameOverMode);
+    }
+}
 
-pub fn init<'a, 'b>(
-    world: &mut World,
-    dispatcher: DispatcherBuilder<'a, 'b>,
-) -> DispatcherBuilder<'a, 'b> {
-    world.add_resource(GameOverModeState::new());
-    dispatcher.add(GameOverModeSystem, "game_over_mode", &[])
+pub struct GameOverMode;
+impl Mode for GameOverMode {
+    fn on_enter(&mut self, world: &mut World) {
+        reset_game(world, false);
+        {
+            let entities = world.entities();
+            let hero_players = world.read::<HeroPlayer>();
+            for (entity, _hero_player) in (&*entities, &hero_players).join() {
+                if let Err(e) = entities.delete(entity) {
+                    println!("Error deleting entity: {:?}", e);
+                }
+            }
+        }
+        let reset_delay = world.read_resource::<GameConfig>().game_over.reset_delay;
+        world
+            .write_resource::<GameOverModeState>()
+            .reset(reset_delay);
+        // The final score gets tallied up from zero before anything else happens.
+        world
+            .write_resource::<score::PlayerScore>()
+            .reset_displayed();
+        world
+            .write_resource::<sound_effects::SoundEffectQueue>()
+            .play(SoundEffectType::Ready);
+    }
+    fn update(&mut self, world: &mut World) {
+        GameOverModeSystem.run_now(&world.res);
+    }
+    fn draw(
+        &mut self,
+        world: &mut World,
+        font: &mut fonts::Font,
+        ctx: &mut Context,
+    ) -> GameResult<()> {
+        draw(world, font, ctx)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameOverPhase {
+    // Counting the displayed score up to the final score.
+    Tally,
+    // Waiting for fire to play again, or for the timeout back to attract mode.
+    Countdown,
 }
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct GameOverModeState {
-    reset_ttl: f32,
+    pub phase: GameOverPhase,
+    pub reset_ttl: f32,
 }
 impl GameOverModeState {
     pub fn new() -> GameOverModeState {
         GameOverModeState {
-            reset_ttl: RESET_TTL_MAX,
+            phase: GameOverPhase::Tally,
+            reset_ttl: 0.0,
         }
     }
-    pub fn reset(&mut self) {
-        self.reset_ttl = RESET_TTL_MAX;
+    pub fn reset(&mut self, reset_ttl: f32) {
+        self.phase = GameOverPhase::Tally;
+        self.reset_ttl = reset_ttl;
     }
     pub fn update(&mut self, delta_time: f32) {
         if self.reset_ttl > 0.0 {
@@ -34,47 +102,35 @@ impl GameOverModeState {
         }
     }
 }
+impl Default for GameOverModeState {
+    fn default() -> GameOverModeState {
+        GameOverModeState::new()
+    }
+}
 
 pub struct GameOverModeSystem;
 impl<'a> System<'a> for GameOverModeSystem {
     type SystemData = (
-        Entities<'a>,
         Fetch<'a, DeltaTime>,
         FetchMut<'a, GameModeManager>,
         FetchMut<'a, GameOverModeState>,
-        FetchMut<'a, sound_effects::SoundEffectQueue>,
-        FetchMut<'a, player_control::Inputs>,
-        FetchMut<'a, score::PlayerScore>,
-        ReadStorage<'a, HeroPlayer>,
+        Fetch<'a, player_control::Inputs>,
+        Fetch<'a, score::PlayerScore>,
+        Fetch<'a, high_scores::HighScoreTable>,
     );
     fn run(&mut self, data: Self::SystemData) {
-        let (
-            entities,
-            delta,
-            mut game_mode,
-            mut game_over_state,
-            mut sounds,
-            mut inputs,
-            mut score,
-            hero_players,
-        ) = data;
-        if game_mode.is_pending(GameMode::GameOver) {
-            reset_game(&entities, &mut inputs, false);
-            for (entity, _hero_player) in (&*entities, &hero_players).join() {
-                if let Err(e) = entities.delete(entity) {
-                    println!("Error deleting entity: {:?}", e);
+        let (delta, mut game_mode, mut game_over_state, inputs, score, high_scores) = data;
+        match game_over_state.phase {
+            GameOverPhase::Tally => {
+                if score.is_tallied() {
+                    if high_scores.qualifies(score.get()) {
+                        game_mode.change(GameMode::EnterInitials);
+                    } else {
+                        game_over_state.phase = GameOverPhase::Countdown;
+                    }
                 }
             }
-            game_over_state.reset();
-            // HACK: Reset displayed score.
-            score.reset_displayed();
-            sounds.play(SoundEffectType::Ready);
-            game_mode.resolve();
-            return;
-        }
-        if game_mode.is_current(GameMode::GameOver) {
-            // HACK: Let displayed score tick back up to the high score before count down to reset.
-            if score.get() == score.get_displayed() {
+            GameOverPhase::Countdown => {
                 game_over_state.update(delta.0);
                 if game_over_state.reset_ttl <= 0.0 {
                     game_mode.change(GameMode::Attract);
@@ -88,11 +144,6 @@ impl<'a> System<'a> for GameOverModeSystem {
 }
 
 pub fn draw(world: &mut World, font: &mut fonts::Font, ctx: &mut Context) -> GameResult<()> {
-    let game_mode = world.read_resource::<GameModeManager>();
-    if !game_mode.is_current(GameMode::GameOver) {
-        return Ok(());
-    }
-
     let player_score = world.read_resource::<score::PlayerScore>();
     // let viewport_state = world.read_resource::<viewport::ViewportState>();
     font.draw(
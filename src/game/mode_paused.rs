@@ -0,0 +1,202 @@
+use specs::*;
+use ggez::*;
+use plugins::*;
+use game::*;
+use SimulationControl;
+
+use super::{GameMode, GameModeManager};
+use super::modes::{self, Mode};
+
+pub struct PausedModePlugin;
+impl Plugin for PausedModePlugin {
+    fn name(&self) -> &'static str {
+        "mode_paused"
+    }
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec!["core", "game", "player_control", "sound_effects"]
+    }
+    fn setup(&self, world: &mut World) {
+        world.add_resource(PausedModeState::new());
+        modes::register(world, GameMode::Paused, PausedMode);
+    }
+}
+
+// An overlay on top of Playing. It freezes the simulation for as long as it's on the stack.
+pub struct PausedMode;
+impl Mode for PausedMode {
+    fn on_enter(&mut self, world: &mut World) {
+        world.write_resource::<player_control::Inputs>().reset();
+        world.write_resource::<PausedModeState>().reset();
+        world.write_resource::<SimulationControl>().paused = true;
+    }
+    fn on_exit(&mut self, world: &mut World) {
+        world.write_resource::<player_control::Inputs>().reset();
+        world.write_resource::<SimulationControl>().paused = false;
+    }
+    fn update(&mut self, world: &mut World) {
+        PausedModeSystem.run_now(&world.res);
+    }
+    fn draw(
+        &mut self,
+        world: &mut World,
+        font: &mut fonts::Font,
+        ctx: &mut Context,
+    ) -> GameResult<()> {
+        draw(world, font, ctx)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MenuItem {
+    Resume,
+    Restart,
+    Settings,
+    Quit,
+    Sound,
+    Back,
+}
+
+const MAIN_MENU: [MenuItem; 4] = [
+    MenuItem::Resume,
+    MenuItem::Restart,
+    MenuItem::Settings,
+    MenuItem::Quit,
+];
+const SETTINGS_MENU: [MenuItem; 2] = [MenuItem::Sound, MenuItem::Back];
+
+pub struct PausedModeState {
+    pub in_settings: bool,
+    pub selected: usize,
+}
+impl PausedModeState {
+    pub fn new() -> PausedModeState {
+        PausedModeState {
+            in_settings: false,
+            selected: 0,
+        }
+    }
+    pub fn reset(&mut self) {
+        self.in_settings = false;
+        self.selected = 0;
+    }
+    pub fn items(&self) -> &'static [MenuItem] {
+        if self.in_settings {
+            &SETTINGS_MENU
+        } else {
+            &MAIN_MENU
+        }
+    }
+    pub fn select_previous(&mut self) {
+        let count = self.items().len();
+        self.selected = (self.selected + count - 1) % count;
+    }
+    pub fn select_next(&mut self) {
+        self.selected = (self.selected + 1) % self.items().len();
+    }
+    fn open_settings(&mut self) {
+        self.in_settings = true;
+        self.selected = 0;
+    }
+    fn close_settings(&mut self) {
+        self.in_settings = false;
+        self.selected = MAIN_MENU
+            .iter()
+            .position(|item| *item == MenuItem::Settings)
+            .unwrap_or(0);
+    }
+}
+impl Default for PausedModeState {
+    fn default() -> PausedModeState {
+        PausedModeState::new()
+    }
+}
+
+pub struct PausedModeSystem;
+impl<'a> System<'a> for PausedModeSystem {
+    type SystemData = (
+        FetchMut<'a, GameModeManager>,
+        FetchMut<'a, PausedModeState>,
+        FetchMut<'a, SimulationControl>,
+        FetchMut<'a, sound_effects::SoundSettings>,
+        FetchMut<'a, player_control::Inputs>,
+    );
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut game_mode, mut paused_state, mut control, mut sounds, mut inputs) = data;
+
+        // Menu actions happen once per press, so use up each input as it's handled.
+        if inputs.pause {
+            inputs.pause = false;
+            if paused_state.in_settings {
+                paused_state.close_settings();
+            } else {
+                game_mode.pop();
+            }
+            return;
+        }
+        if inputs.up {
+            inputs.up = false;
+            paused_state.select_previous();
+        }
+        if inputs.down {
+            inputs.down = false;
+            paused_state.select_next();
+        }
+        if inputs.fire || inputs.special {
+            inputs.fire = false;
+            inputs.special = false;
+            match paused_state.items()[paused_state.selected] {
+                MenuItem::Resume => game_mode.pop(),
+                MenuItem::Restart => game_mode.change(GameMode::Playing),
+                MenuItem::Settings => paused_state.open_settings(),
+                MenuItem::Quit => control.quit = true,
+                MenuItem::Sound => sounds.muted = !sounds.muted,
+                MenuItem::Back => paused_state.close_settings(),
+            }
+        }
+    }
+}
+
+pub fn draw(world: &mut World, font: &mut fonts::Font, ctx: &mut Context) -> GameResult<()> {
+    let paused_state = world.read_resource::<PausedModeState>();
+    let sounds = world.read_resource::<sound_effects::SoundSettings>();
+    let mut text = String::from(if paused_state.in_settings {
+        "Settings\n\n"
+    } else {
+        "Paused\n\n"
+    });
+    for (idx, item) in paused_state.items().iter().enumerate() {
+        text.push_str(if idx == paused_state.selected {
+            "> "
+        } else {
+            "  "
+        });
+        text.push_str(match *item {
+            MenuItem::Resume => "Resume",
+            MenuItem::Restart => "Restart",
+            MenuItem::Settings => "Settings",
+            MenuItem::Quit => "Quit",
+            MenuItem::Sound => {
+                if sounds.muted {
+                    "Sound: Off"
+                } else {
+                    "Sound: On"
+                }
+            }
+            MenuItem::Back => "Back",
+        });
+        text.push('\n');
+    }
+
+    font.draw(
+        ctx,
+        &text,
+        fonts::DrawOptions {
+            x: -200.0,
+            y: -200.0,
+            scale: 3.0,
+            ..Default::default()
+        },
+    )?;
+
+    Ok(())
+}
@@ -1,5 +1,5 @@
 use std::f32::consts::PI;
-use rand;
+use rand::Rng;
 
 use specs::*;
 use ggez::*;
@@ -8,25 +8,86 @@ use plugins::*;
 use game::*;
 use game::sound_effects::SoundEffectType;
 use DeltaTime;
+use config::GameConfig;
 
-use super::{prefabs, reset_game, GameMode, GameModeManager, HeroPlanet, HeroPlayer};
+use super::{prefabs, reset_game, Asteroid, GameMode, GameModeManager, HeroPlanet, HeroPlayer};
+use super::modes::{self, Mode};
 
-const PLAYER_REGEN_PER_SECOND: f32 = 15.0;
-
-pub fn init<'a, 'b>(
-    world: &mut World,
-    dispatcher: DispatcherBuilder<'a, 'b>,
-) -> DispatcherBuilder<'a, 'b> {
-    world.add_resource(PlayingModeState::new());
-    dispatcher.add(PlayingModeSystem, "playing_mode", &[])
+pub struct PlayingModePlugin;
+impl Plugin for PlayingModePlugin {
+    fn name(&self) -> &'static str {
+        "mode_playing"
+    }
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec![
+            "game",
+            "config",
+            "rng",
+            "player_control",
+            "position_motion",
+            "collision",
+            "health_damage",
+            "despawn",
+            "score",
+            "sound_effects",
+            "power_ups",
+        ]
+    }
+    fn setup(&self, world: &mut World) {
+        world.add_resource(PlayingModeState::new());
+        world
+            .write_resource::<despawn::DespawnEventQueue>()
+            .register_reader("mode_playing");
+        world
+            .write_resource::<collision::ContactEvents>()
+            .register_reader("mode_playing");
+        modes::register(world, GameMode::Playing, PlayingMode);
+    }
 }
 
-pub fn draw(world: &mut World, font: &mut fonts::Font, ctx: &mut Context) -> GameResult<()> {
-    let game_mode = world.read_resource::<GameModeManager>();
-    if !game_mode.is_current(GameMode::Playing) {
-        return Ok(());
+pub struct PlayingMode;
+impl Mode for PlayingMode {
+    fn on_enter(&mut self, world: &mut World) {
+        reset_game(world, true);
+        // Only read while playing, so skip whatever piled up since the last game.
+        world
+            .write_resource::<despawn::DespawnEventQueue>()
+            .register_reader("mode_playing");
+        world
+            .write_resource::<collision::ContactEvents>()
+            .register_reader("mode_playing");
+        world.write_resource::<PlayingModeState>().reset();
+        world.write_resource::<score::PlayerScore>().reset();
+        world.write_resource::<score::Scores>().reset();
+        world.write_resource::<power_ups::ActivePowerUps>().reset();
+        {
+            let entities = world.entities();
+            let lazy = world.read_resource::<LazyUpdate>();
+            let config = world.read_resource::<GameConfig>();
+            prefabs::player(&config, entities.create(), &lazy);
+            prefabs::planet(&config, entities.create(), &lazy);
+        }
+        world
+            .write_resource::<sound_effects::SoundEffectQueue>()
+            .play(SoundEffectType::Ready);
+    }
+    fn update(&mut self, world: &mut World) {
+        PlayingModeSystem.run_now(&world.res);
     }
+    fn draw(
+        &mut self,
+        world: &mut World,
+        font: &mut fonts::Font,
+        ctx: &mut Context,
+    ) -> GameResult<()> {
+        let start = profiler::start(world);
+        hud::draw(world, font, ctx)?;
+        profiler::finish(world, "draw_hud", start);
+        draw(world, font, ctx)
+    }
+}
 
+pub fn draw(world: &mut World, font: &mut fonts::Font, ctx: &mut Context) -> GameResult<()> {
     let playing_state = world.read_resource::<PlayingModeState>();
     if playing_state.ready_delay > 0.0 {
         // let viewport_state = world.read_resource::<viewport::ViewportState>();
@@ -46,7 +107,7 @@ pub fn draw(world: &mut World, font: &mut fonts::Font, ctx: &mut Context) -> Gam
 }
 
 pub struct PlayingModeState {
-    ready_delay: f32,
+    pub ready_delay: f32,
 }
 impl PlayingModeState {
     pub fn new() -> PlayingModeState {
@@ -69,9 +130,9 @@ impl<'a> System<'a> for PlayingModeSystem {
         Fetch<'a, LazyUpdate>,
         Fetch<'a, DeltaTime>,
         FetchMut<'a, health_damage::DamageEventQueue>,
-        FetchMut<'a, despawn::DespawnEventQueue>,
+        Fetch<'a, despawn::DespawnEventQueue>,
         FetchMut<'a, sound_effects::SoundEffectQueue>,
-        Fetch<'a, collision::Collisions>,
+        Fetch<'a, collision::ContactEvents>,
         FetchMut<'a, viewport::ViewportState>,
         FetchMut<'a, GameModeManager>,
         FetchMut<'a, PlayingModeState>,
@@ -83,9 +144,13 @@ impl<'a> System<'a> for PlayingModeSystem {
         WriteStorage<'a, thruster::ThrusterSet>,
         WriteStorage<'a, guns::Gun>,
         ReadStorage<'a, position_motion::Position>,
-        ReadStorage<'a, collision::Collidable>,
+        ReadStorage<'a, position_motion::Velocity>,
+        ReadStorage<'a, Asteroid>,
+        Fetch<'a, collision::SpatialIndex>,
         ReadStorage<'a, sprites::Sprite>,
         ReadStorage<'a, metadata::Tags>,
+        FetchMut<'a, rng::GameRng>,
+        Fetch<'a, GameConfig>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
@@ -94,9 +159,9 @@ impl<'a> System<'a> for PlayingModeSystem {
             lazy,
             delta,
             mut damages,
-            mut despawns,
+            despawns,
             mut sounds,
-            collisions,
+            contact_events,
             mut viewport,
             mut game_mode,
             mut playing_state,
@@ -108,23 +173,18 @@ impl<'a> System<'a> for PlayingModeSystem {
             mut thruster_set,
             mut gun,
             positions,
-            collidables,
+            velocities,
+            asteroids,
+            index,
             sprites,
             tags,
+            mut rng,
+            config,
         ) = data;
 
-        if game_mode.is_pending(GameMode::Playing) {
-            reset_game(&entities, &mut inputs, true);
-            playing_state.reset();
-            player_score.reset();
-            prefabs::player(entities.create(), &lazy);
-            prefabs::planet(entities.create(), &lazy);
-            sounds.play(SoundEffectType::Ready);
-            game_mode.resolve();
-            return;
-        }
-
-        if !game_mode.is_current(GameMode::Playing) {
+        if inputs.pause {
+            inputs.pause = false;
+            game_mode.push(GameMode::Paused);
             return;
         }
 
@@ -132,9 +192,10 @@ impl<'a> System<'a> for PlayingModeSystem {
 
         if playing_state.ready_delay <= 0.0 {
             // Quick & dirty ramp up of difficulty relative to current score
-            let asteroid_spawn_chance = 0.025 + (player_score.get() as f32 / 1500000.0);
-            if rand::random::<f32>() < asteroid_spawn_chance {
-                prefabs::asteroid(&positions, &collidables, entities.create(), &lazy);
+            let asteroid_spawn_chance = config.asteroids.spawn_chance
+                + (player_score.get() as f32 / config.asteroids.spawn_ramp_points);
+            if rng.gameplay.gen::<f32>() < asteroid_spawn_chance * delta.0 {
+                prefabs::asteroid(&mut rng.gameplay, &config, &index, entities.create(), &lazy);
             }
         }
 
@@ -147,7 +208,7 @@ impl<'a> System<'a> for PlayingModeSystem {
         for (entity, _hero_player, health) in (&*entities, &hero_players, &healths).join() {
             // Slowly regenerate the player while alive
             if health.health < health.max_health {
-                damages.heal(entity, entity, PLAYER_REGEN_PER_SECOND * delta.0);
+                damages.heal(entity, entity, config.player.regen_per_second * delta.0);
             }
             hero_player_alive = true;
         }
@@ -157,6 +218,10 @@ impl<'a> System<'a> for PlayingModeSystem {
 
         for (thruster_set, gun) in (&mut thruster_set, &mut gun).join() {
             gun.firing = inputs.fire;
+            if inputs.special {
+                inputs.special = false;
+                gun.next_weapon();
+            }
 
             if let Some(lat_thruster) = thruster_set.0.get_mut("lateral") {
                 lat_thruster.throttle = if inputs.right {
@@ -179,7 +244,7 @@ impl<'a> System<'a> for PlayingModeSystem {
             }
         }
 
-        for despawn_event in &despawns.0 {
+        for despawn_event in despawns.read("mode_playing") {
             let entity = despawn_event.entity;
             if let (Some(tags), Some(position), Some(sprite)) =
                 (tags.get(entity), positions.get(entity), sprites.get(entity))
@@ -189,34 +254,41 @@ impl<'a> System<'a> for PlayingModeSystem {
                         &entities,
                         &lazy,
                         &mut sounds,
-                        &despawn_event,
+                        &mut rng,
+                        &config,
+                        despawn_event,
                         tag,
                         &position,
                         &sprite,
+                        velocities.get(entity),
+                        asteroids.get(entity),
                     );
                 }
             }
         }
 
-        for (a_entity, a_tags) in (&*entities, &tags).join() {
-            for &a_tag in &a_tags.0 {
-                if let Some(ent_collisions) = collisions.get(&a_entity) {
-                    for b_entity in ent_collisions.iter() {
-                        if let Some(b_tags) = tags.get(*b_entity) {
-                            for &b_tag in &b_tags.0 {
-                                self.handle_collision(
-                                    &lazy,
-                                    &mut player_score,
-                                    &mut damages,
-                                    &mut despawns,
-                                    &mut sounds,
-                                    &mut viewport,
-                                    &a_tag,
-                                    &b_tag,
-                                    &a_entity,
-                                    &b_entity,
-                                );
-                            }
+        // Only react as things first hit, rather than on every tick they stay touching.
+        for event in contact_events.read("mode_playing") {
+            let contact = match *event {
+                collision::ContactEvent::Began(ref contact) => contact,
+                _ => continue,
+            };
+            for &(a_entity, b_entity) in &[(contact.a, contact.b), (contact.b, contact.a)] {
+                if let (Some(a_tags), Some(b_tags)) = (tags.get(a_entity), tags.get(b_entity)) {
+                    for &a_tag in &a_tags.0 {
+                        for &b_tag in &b_tags.0 {
+                            self.handle_collision(
+                                &lazy,
+                                &config,
+                                &mut player_score,
+                                &mut damages,
+                                &mut sounds,
+                                &mut viewport,
+                                &a_tag,
+                                &b_tag,
+                                &a_entity,
+                                &b_entity,
+                            );
                         }
                     }
                 }
@@ -231,10 +303,14 @@ impl PlayingModeSystem {
         entities: &Entities,
         lazy: &LazyUpdate,
         sounds: &mut sound_effects::SoundEffectQueue,
+        rng: &mut rng::GameRng,
+        config: &GameConfig,
         despawn_event: &despawn::DespawnEvent,
         tag: &str,
         position: &position_motion::Position,
         sprite: &sprites::Sprite,
+        velocity: Option<&position_motion::Velocity>,
+        asteroid: Option<&Asteroid>,
     ) {
         if despawn_event.reason == despawn::DespawnReason::Health {
             if tag == "asteroid" {
@@ -265,6 +341,28 @@ impl PlayingModeSystem {
                         ..Default::default()
                     },
                 );
+
+                if let Some(&Asteroid(size)) = asteroid {
+                    prefabs::asteroid_fragments(
+                        &mut rng.gameplay,
+                        config,
+                        size,
+                        position,
+                        velocity,
+                        entities,
+                        lazy,
+                    );
+                }
+                if rng.gameplay.gen::<f32>() < config.power_ups.drop_chance {
+                    prefabs::power_up(
+                        &mut rng.gameplay,
+                        config,
+                        position,
+                        velocity,
+                        entities.create(),
+                        lazy,
+                    );
+                }
             }
         }
     }
@@ -272,9 +370,9 @@ impl PlayingModeSystem {
     fn handle_collision(
         &mut self,
         _lazy: &LazyUpdate,
+        config: &GameConfig,
         _player_score: &mut score::PlayerScore,
         damages: &mut health_damage::DamageEventQueue,
-        _despawns: &mut despawn::DespawnEventQueue,
         sounds: &mut sound_effects::SoundEffectQueue,
         viewport: &mut viewport::ViewportState,
         a_tag: &str,
@@ -283,13 +381,12 @@ impl PlayingModeSystem {
         b_entity: &Entity,
     ) {
         match (a_tag, b_tag) {
+            // The bounce works out how much damage these do.
             ("asteroid", "player") => {
-                damages.hurt_mutual(*a_entity, *b_entity, 100.0);
                 viewport.shake(16.0, 0.3);
                 sounds.play(SoundEffectType::Shield);
             }
             ("asteroid", "planet") => {
-                damages.hurt_mutual(*a_entity, *b_entity, 100.0);
                 viewport.shake(16.0, 0.3);
                 sounds.play(SoundEffectType::PlanetHit);
             }
@@ -297,7 +394,7 @@ impl PlayingModeSystem {
                 // damages.hurt_mutual(*a_entity, *b_entity, 10.0);
             }
             ("player_bullet", "enemy") => {
-                damages.hurt_mutual(*a_entity, *b_entity, 100.0);
+                damages.hurt_mutual(*a_entity, *b_entity, config.collisions.damage);
             }
             (&_, _) => (),
         }
@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::mem;
+use specs::*;
+use ggez::*;
+use plugins::*;
+
+use super::{GameMode, GameModeManager, ModeRequest};
+
+// One screen's worth of game logic. Only the mode on top of the stack gets updated, but every
+// mode in the stack is drawn, bottom first, so overlays like the pause menu sit on top of the
+// mode they cover.
+pub trait Mode: Send + Sync {
+    fn on_enter(&mut self, _world: &mut World) {}
+    fn on_exit(&mut self, _world: &mut World) {}
+    // Ask for transitions through GameModeManager - they're applied once the update is done.
+    fn update(&mut self, world: &mut World);
+    fn draw(
+        &mut self,
+        _world: &mut World,
+        _font: &mut fonts::Font,
+        _ctx: &mut Context,
+    ) -> GameResult<()> {
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ModeEvent {
+    Entered(GameMode),
+    Exited(GameMode),
+}
+
+// Transitions applied during the last tick, for other plugins that want to react to them. Each
+// batch is visible to systems for one tick.
+#[derive(Debug)]
+pub struct ModeEvents(pub Vec<ModeEvent>);
+impl ModeEvents {
+    pub fn new() -> ModeEvents {
+        ModeEvents(Vec::new())
+    }
+    pub fn entered(&self, mode: GameMode) -> bool {
+        self.0.contains(&ModeEvent::Entered(mode))
+    }
+    pub fn exited(&self, mode: GameMode) -> bool {
+        self.0.contains(&ModeEvent::Exited(mode))
+    }
+}
+impl Default for ModeEvents {
+    fn default() -> ModeEvents {
+        ModeEvents::new()
+    }
+}
+
+// Holds the Mode for each GameMode. Mode plugins register theirs during setup.
+pub struct ModeMachine {
+    modes: HashMap<GameMode, Box<dyn Mode>>,
+}
+impl ModeMachine {
+    pub fn new() -> ModeMachine {
+        ModeMachine {
+            modes: HashMap::new(),
+        }
+    }
+    pub fn register<M: Mode + 'static>(&mut self, mode: GameMode, implementation: M) {
+        self.modes.insert(mode, Box::new(implementation));
+    }
+    fn get(&mut self, mode: GameMode) -> Option<&mut Box<dyn Mode>> {
+        self.modes.get_mut(&mode)
+    }
+}
+impl Default for ModeMachine {
+    fn default() -> ModeMachine {
+        ModeMachine::new()
+    }
+}
+
+pub fn register<M: Mode + 'static>(world: &mut World, mode: GameMode, implementation: M) {
+    world
+        .write_resource::<ModeMachine>()
+        .register(mode, implementation);
+}
+
+// Modes need the whole world, including the resource the machine itself lives in, so take the
+// machine out while it's working and put it back afterwards.
+fn with_machine<F: FnOnce(&mut ModeMachine, &mut World) -> R, R>(world: &mut World, f: F) -> R {
+    let mut machine = mem::take(&mut *world.write_resource::<ModeMachine>());
+    let result = f(&mut machine, world);
+    *world.write_resource::<ModeMachine>() = machine;
+    result
+}
+
+// Updates the mode on top of the stack, then applies whatever transitions were asked for.
+pub fn update(world: &mut World) {
+    world.write_resource::<ModeEvents>().0.clear();
+    with_machine(world, |machine, world| {
+        let top = world.read_resource::<GameModeManager>().current();
+        if let Some(mode) = top.and_then(|mode| machine.get(mode)) {
+            mode.update(world);
+        }
+        apply_requests(machine, world);
+    });
+}
+
+fn apply_requests(machine: &mut ModeMachine, world: &mut World) {
+    // Entering or exiting a mode can ask for another transition, so keep going until it settles.
+    loop {
+        let requests = world.write_resource::<GameModeManager>().take_requests();
+        if requests.is_empty() {
+            return;
+        }
+        for request in requests {
+            match request {
+                ModeRequest::Change(mode) => {
+                    while pop(machine, world) {}
+                    push(machine, world, mode);
+                }
+                ModeRequest::Push(mode) => push(machine, world, mode),
+                ModeRequest::Pop => {
+                    pop(machine, world);
+                }
+            }
+        }
+    }
+}
+
+fn push(machine: &mut ModeMachine, world: &mut World, mode: GameMode) {
+    world.write_resource::<GameModeManager>().stack.push(mode);
+    if let Some(implementation) = machine.get(mode) {
+        implementation.on_enter(world);
+    }
+    world
+        .write_resource::<ModeEvents>()
+        .0
+        .push(ModeEvent::Entered(mode));
+}
+
+fn pop(machine: &mut ModeMachine, world: &mut World) -> bool {
+    let popped = world.write_resource::<GameModeManager>().stack.pop();
+    match popped {
+        Some(mode) => {
+            if let Some(implementation) = machine.get(mode) {
+                implementation.on_exit(world);
+            }
+            world
+                .write_resource::<ModeEvents>()
+                .0
+                .push(ModeEvent::Exited(mode));
+            true
+        }
+        None => false,
+    }
+}
+
+pub fn draw(world: &mut World, font: &mut fonts::Font, ctx: &mut Context) -> GameResult<()> {
+    with_machine(world, |machine, world| {
+        let stack = world.read_resource::<GameModeManager>().stack.clone();
+        for mode in stack {
+            if let Some(implementation) = machine.get(mode) {
+                implementation.draw(world, font, ctx)?;
+            }
+        }
+        Ok(())
+    })
+}
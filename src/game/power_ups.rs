@@ -0,0 +1,197 @@
+use std::collections::BTreeMap;
+use specs::*;
+use plugins::*;
+use DeltaTime;
+use config::GameConfig;
+
+use super::{HeroPlanet, HeroPlayer};
+
+pub struct PowerUpsPlugin;
+impl Plugin for PowerUpsPlugin {
+    fn name(&self) -> &'static str {
+        "power_ups"
+    }
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec![
+            "game",
+            "config",
+            "collision",
+            "health_damage",
+            "despawn",
+            "guns",
+            "score",
+        ]
+    }
+    fn setup(&self, world: &mut World) {
+        world.add_resource(ActivePowerUps::new());
+        world.register::<PowerUp>();
+        world
+            .write_resource::<collision::ContactEvents>()
+            .register_reader("power_ups");
+    }
+    fn systems<'a, 'b>(&self, systems: &mut SystemRegistry<'a, 'b>) {
+        systems.add(Stage::Damage, PowerUpSystem, "power_ups", &[]);
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum PowerUpType {
+    RapidFire,
+    SpreadShot,
+    ShieldRecharge,
+    PlanetRepair,
+    ScoreMultiplier,
+}
+pub const POWER_UP_TYPES: [PowerUpType; 5] = [
+    PowerUpType::RapidFire,
+    PowerUpType::SpreadShot,
+    PowerUpType::ShieldRecharge,
+    PowerUpType::PlanetRepair,
+    PowerUpType::ScoreMultiplier,
+];
+impl PowerUpType {
+    pub fn name(self) -> &'static str {
+        match self {
+            PowerUpType::RapidFire => "Rapid fire",
+            PowerUpType::SpreadShot => "Spread shot",
+            PowerUpType::ShieldRecharge => "Shield recharge",
+            PowerUpType::PlanetRepair => "Planet repair",
+            PowerUpType::ScoreMultiplier => "Score multiplier",
+        }
+    }
+}
+
+// A pickup that gives the player a power-up when they fly into it.
+#[derive(Component, Debug)]
+pub struct PowerUp(pub PowerUpType);
+
+// Seconds left on each power-up the player has.
+#[derive(Debug, Default)]
+pub struct ActivePowerUps(pub BTreeMap<PowerUpType, f32>);
+impl ActivePowerUps {
+    pub fn new() -> ActivePowerUps {
+        Default::default()
+    }
+    pub fn reset(&mut self) {
+        self.0.clear();
+    }
+    pub fn is_active(&self, power_up: PowerUpType) -> bool {
+        self.0.contains_key(&power_up)
+    }
+}
+
+// Hands out power-ups the player flies into, counts them down, and keeps what they change in
+// step with which ones are still going.
+pub struct PowerUpSystem;
+impl<'a> System<'a> for PowerUpSystem {
+    type SystemData = (
+        Entities<'a>,
+        Fetch<'a, DeltaTime>,
+        Fetch<'a, GameConfig>,
+        Fetch<'a, collision::ContactEvents>,
+        FetchMut<'a, ActivePowerUps>,
+        FetchMut<'a, health_damage::DamageEventQueue>,
+        FetchMut<'a, despawn::DespawnEventQueue>,
+        FetchMut<'a, score::PlayerScore>,
+        ReadStorage<'a, PowerUp>,
+        ReadStorage<'a, HeroPlayer>,
+        ReadStorage<'a, HeroPlanet>,
+        ReadStorage<'a, health_damage::Health>,
+        WriteStorage<'a, guns::Gun>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            delta,
+            config,
+            contact_events,
+            mut active,
+            mut damages,
+            mut despawns,
+            mut player_score,
+            power_ups,
+            hero_players,
+            hero_planets,
+            healths,
+            mut guns,
+        ) = data;
+        let delta = delta.0;
+        let tunables = &config.power_ups;
+
+        for event in contact_events.read("power_ups") {
+            let contact = match *event {
+                collision::ContactEvent::Began(ref contact) => contact,
+                _ => continue,
+            };
+            for &(pickup, player) in &[(contact.a, contact.b), (contact.b, contact.a)] {
+                if let (Some(power_up), Some(_)) = (power_ups.get(pickup), hero_players.get(player))
+                {
+                    // Picking up one that's still going starts its clock over.
+                    active.0.insert(power_up.0, tunables.duration);
+                    despawns.despawn(pickup, despawn::DespawnReason::Collision);
+                }
+            }
+        }
+
+        for remaining in active.0.values_mut() {
+            *remaining -= delta;
+        }
+        active.0.retain(|_, remaining| *remaining > 0.0);
+
+        let rapid_fire = active.is_active(PowerUpType::RapidFire);
+        let spread_shot = active.is_active(PowerUpType::SpreadShot);
+        for (_player, gun) in (&hero_players, &mut guns).join() {
+            gun.period_scale = if rapid_fire {
+                tunables.rapid_fire_period_scale
+            } else {
+                1.0
+            };
+            gun.extra_spread = if spread_shot {
+                tunables.spread_extra_shots
+            } else {
+                0
+            };
+        }
+
+        if active.is_active(PowerUpType::ShieldRecharge) {
+            for (entity, _player, health) in (&*entities, &hero_players, &healths).join() {
+                repair(
+                    &mut damages,
+                    entity,
+                    health,
+                    tunables.shield_recharge_per_second * delta,
+                );
+            }
+        }
+        if active.is_active(PowerUpType::PlanetRepair) {
+            for (entity, _planet, health) in (&*entities, &hero_planets, &healths).join() {
+                repair(
+                    &mut damages,
+                    entity,
+                    health,
+                    tunables.planet_repair_per_second * delta,
+                );
+            }
+        }
+
+        player_score.set_multiplier(if active.is_active(PowerUpType::ScoreMultiplier) {
+            tunables.score_multiplier
+        } else {
+            1
+        });
+    }
+}
+
+// Heals up to full health, but no further.
+fn repair(
+    damages: &mut health_damage::DamageEventQueue,
+    entity: Entity,
+    health: &health_damage::Health,
+    amount: f32,
+) {
+    let amount = amount.min(health.max_health - health.health);
+    if amount > 0.0 {
+        damages.heal(entity, entity, amount);
+    }
+}
@@ -1,60 +1,77 @@
 use std::f32::consts::PI;
-use rand;
+use rand::Rng;
 
 use specs::*;
 use ggez::graphics::*;
 use plugins::*;
-use super::{HeroPlanet, HeroPlayer};
+use config::GameConfig;
+use super::{Asteroid, AsteroidSize, HeroPlanet, HeroPlayer};
+use super::power_ups::{PowerUp, POWER_UP_TYPES};
 
-pub fn player(entity: Entity, lazy: &LazyUpdate) {
+pub fn player(config: &GameConfig, entity: Entity, lazy: &LazyUpdate) {
+    let playfield = &config.playfield;
+    let sprite = sprites::Sprite {
+        shape: sprites::Shape::Player,
+        scale: Point2::new(50.0, 50.0),
+        ..Default::default()
+    };
     LazyBuilder { entity, lazy }
         .with(metadata::Name("player"))
         .with(metadata::Tags::new(vec!["player", "friend"]))
         .with(HeroPlayer)
         .with(position_motion::Position {
-            y: (viewport::PLAYFIELD_HEIGHT / 2.0) - 200.0,
+            y: (playfield.height / 2.0) - 200.0,
             ..Default::default()
         })
         .with(position_motion::PositionBounds(Rect::new(
-            0.0 - viewport::PLAYFIELD_WIDTH / 2.0 + 25.0,
-            0.0 - viewport::PLAYFIELD_HEIGHT / 2.0 + 5.0,
-            viewport::PLAYFIELD_WIDTH - 50.0,
-            viewport::PLAYFIELD_HEIGHT - 10.0,
+            0.0 - playfield.width / 2.0 + 25.0,
+            0.0 - playfield.height / 2.0 + 5.0,
+            playfield.width - 50.0,
+            playfield.height - 10.0,
         )))
         .with(position_motion::Velocity {
             ..Default::default()
         })
-        .with(simple_physics::SpeedLimit(800.0))
-        .with(simple_physics::Friction(6000.0))
-        .with(thruster::ThrusterSet(hashmap!{
+        .with(simple_physics::SpeedLimit(config.player.speed_limit))
+        .with(simple_physics::Friction(config.player.friction))
+        .with(thruster::ThrusterSet(btreemap! {
             "longitudinal" => thruster::Thruster {
-                thrust: 10000.0,
+                thrust: config.player.thrust,
                 throttle: 0.0,
                 angle: 0.0,
             },
             "lateral" => thruster::Thruster {
-                thrust: 12500.0,
+                thrust: config.player.lateral_thrust,
                 throttle: 0.0,
                 angle: PI * 0.5,
             },
         }))
-        .with(guns::Gun {
-            period: 0.2,
+        .with(guns::Gun::new(
+            config
+                .player
+                .weapons
+                .iter()
+                .filter_map(|name| config.weapon(name))
+                .cloned()
+                .collect(),
+        ))
+        .with(collision::Collidable::from_sprite(&sprite).with_layer(
+            collision::LAYER_PLAYER,
+            collision::LAYER_ASTEROID | collision::LAYER_PLANET | collision::LAYER_POWER_UP,
+        ))
+        .with(bounce::BounceOnCollision {
+            mass: 5.0,
             ..Default::default()
         })
-        .with(collision::Collidable { size: 50.0 })
-        .with(bounce::BounceOnCollision { mass: 5.0 })
+        .with(config.collisions.player_impact.clone())
         .with(health_damage::Health::new(1000.0))
-        .with(sprites::Sprite {
-            shape: sprites::Shape::Player,
-            scale: Point2::new(50.0, 50.0),
-            ..Default::default()
-        })
+        .with(sprite)
         .with(player_control::PlayerControl)
+        .with(score::ScoresPoints(score::PLAYER_ONE))
         .build();
 }
 
-pub fn planet(entity: Entity, lazy: &LazyUpdate) {
+pub fn planet(config: &GameConfig, entity: Entity, lazy: &LazyUpdate) {
     LazyBuilder { entity, lazy }
         .with(HeroPlanet)
         .with(metadata::Tags::new(vec!["planet", "friend"]))
@@ -72,60 +89,197 @@ pub fn planet(entity: Entity, lazy: &LazyUpdate) {
             scale: Point2::new(3000.0, 3000.0),
             ..Default::default()
         })
-        .with(collision::Collidable { size: 3000.0 })
+        .with(collision::Collidable::circle(3000.0).with_layer(
+            collision::LAYER_PLANET,
+            collision::LAYER_ASTEROID | collision::LAYER_PLAYER,
+        ))
         .with(simple_physics::SpeedLimit(0.0))
         .with(simple_physics::Friction(100000.0))
-        .with(bounce::BounceOnCollision { mass: 100000.0 })
+        .with(bounce::BounceOnCollision {
+            mass: 100000.0,
+            ..Default::default()
+        })
+        .with(config.collisions.planet_impact.clone())
         .with(health_damage::Health::new(5000.0))
         .build();
 }
 
-const HW: f32 = viewport::PLAYFIELD_WIDTH / 2.0;
-const HH: f32 = viewport::PLAYFIELD_HEIGHT / 2.0;
-
-pub fn asteroid(
-    positions: &ReadStorage<position_motion::Position>,
-    collidables: &ReadStorage<collision::Collidable>,
+pub fn asteroid<R: Rng>(
+    rng: &mut R,
+    config: &GameConfig,
+    index: &collision::SpatialIndex,
     entity: Entity,
     lazy: &LazyUpdate,
 ) {
-    let size = 25.0 + 150.0 * rand::random::<f32>();
-    let x = 0.0 - HW + (viewport::PLAYFIELD_WIDTH / 8.0) * (rand::random::<f32>() * 8.0);
-    let y = 0.0 - HH - size;
+    let playfield = &config.playfield;
+    let (hw, hh) = (playfield.width / 2.0, playfield.height / 2.0);
+    let size = match rng.gen_range(0, 3) {
+        0 => AsteroidSize::Big,
+        1 => AsteroidSize::Medium,
+        _ => AsteroidSize::Small,
+    };
+    let diameter = random_diameter(rng, size);
+    let x = 0.0 - hw + (playfield.width / 8.0) * (rng.gen::<f32>() * 8.0);
+    let y = 0.0 - hh - diameter;
 
-    if !collision::is_empty_at(&positions, &collidables, x, y, size) {
+    if !collision::is_empty_at(index, x, y, diameter) {
         return;
     }
 
-    LazyBuilder { entity, lazy }
-        .with(metadata::Tags::new(vec!["asteroid", "enemy"]))
-        .with(position_motion::Position {
+    let velocity = position_motion::Velocity {
+        x: 50.0 - 100.0 * rng.gen::<f32>(),
+        y: 50.0 + 100.0 * rng.gen::<f32>(),
+        r: PI * rng.gen::<f32>(),
+    };
+    build_asteroid(
+        config,
+        size,
+        diameter,
+        position_motion::Position {
             x,
             y,
             ..Default::default()
+        },
+        velocity,
+        entity,
+        lazy,
+    );
+}
+
+// Breaks a destroyed asteroid into the next size down, if there is one. The pieces carry on with
+// the asteroid's momentum, and fly apart from each other evenly around where it was.
+pub fn asteroid_fragments<R: Rng>(
+    rng: &mut R,
+    config: &GameConfig,
+    size: AsteroidSize,
+    position: &position_motion::Position,
+    velocity: Option<&position_motion::Velocity>,
+    entities: &EntitiesRes,
+    lazy: &LazyUpdate,
+) {
+    let (fragment_size, count) = match size.fragments() {
+        Some(fragments) => fragments,
+        None => return,
+    };
+    let (vx, vy) = velocity.map_or((0.0, 0.0), |velocity| (velocity.x, velocity.y));
+    let first_angle = 2.0 * PI * rng.gen::<f32>();
+    for idx in 0..count {
+        let angle = first_angle + 2.0 * PI * idx as f32 / count as f32;
+        let (dx, dy) = (angle.cos(), angle.sin());
+        let diameter = random_diameter(rng, fragment_size);
+        let speed = config.asteroids.fragment_speed * (0.75 + 0.5 * rng.gen::<f32>());
+        build_asteroid(
+            config,
+            fragment_size,
+            diameter,
+            position_motion::Position {
+                x: position.x + dx * diameter / 2.0,
+                y: position.y + dy * diameter / 2.0,
+                r: position.r,
+            },
+            position_motion::Velocity {
+                x: vx + dx * speed,
+                y: vy + dy * speed,
+                r: PI * (2.0 * rng.gen::<f32>() - 1.0),
+            },
+            entities.create(),
+            lazy,
+        );
+    }
+}
+
+// A pickup of some random power-up, drifting off from where an asteroid was destroyed until the
+// player flies into it or it fades away.
+pub fn power_up<R: Rng>(
+    rng: &mut R,
+    config: &GameConfig,
+    position: &position_motion::Position,
+    velocity: Option<&position_motion::Velocity>,
+    entity: Entity,
+    lazy: &LazyUpdate,
+) {
+    let playfield = &config.playfield;
+    let (hw, hh) = (playfield.width / 2.0, playfield.height / 2.0);
+    let power_up = POWER_UP_TYPES[rng.gen_range(0, POWER_UP_TYPES.len())];
+    let (vx, vy) = velocity.map_or((0.0, 0.0), |velocity| (velocity.x, velocity.y));
+    let sprite = sprites::Sprite {
+        shape: sprites::Shape::PowerUp,
+        scale: Point2::new(40.0, 40.0),
+        ..Default::default()
+    };
+    LazyBuilder { entity, lazy }
+        .with(metadata::Tags::new(vec!["power_up"]))
+        .with(PowerUp(power_up))
+        .with(position_motion::Position {
+            x: position.x,
+            y: position.y,
+            ..Default::default()
         })
         .with(position_motion::Velocity {
-            x: 50.0 - 100.0 * rand::random::<f32>(),
-            y: 50.0 + 100.0 * rand::random::<f32>(),
-            r: PI * rand::random::<f32>(),
+            x: vx / 2.0,
+            y: vy / 2.0,
+            r: PI,
         })
-        .with(collision::Collidable { size })
+        .with(
+            collision::Collidable::from_sprite(&sprite)
+                .with_layer(collision::LAYER_POWER_UP, collision::LAYER_PLAYER),
+        )
+        .with(sprite)
+        .with(despawn::Timeout(config.power_ups.pickup_lifetime))
+        .with(despawn::DespawnBounds(Rect::new(
+            0.0 - hw - 200.0,
+            0.0 - hh - 200.0,
+            playfield.width + 400.0,
+            playfield.height + 400.0,
+        )))
+        .build();
+}
+
+fn random_diameter<R: Rng>(rng: &mut R, size: AsteroidSize) -> f32 {
+    let (min, max) = size.diameters();
+    min + (max - min) * rng.gen::<f32>()
+}
+
+fn build_asteroid(
+    config: &GameConfig,
+    size: AsteroidSize,
+    diameter: f32,
+    position: position_motion::Position,
+    velocity: position_motion::Velocity,
+    entity: Entity,
+    lazy: &LazyUpdate,
+) {
+    let playfield = &config.playfield;
+    let (hw, hh) = (playfield.width / 2.0, playfield.height / 2.0);
+    LazyBuilder { entity, lazy }
+        .with(metadata::Tags::new(vec!["asteroid", "enemy"]))
+        .with(Asteroid(size))
+        .with(position)
+        .with(velocity)
+        // Asteroids pass through each other, so there's no need to check them against each other.
+        .with(collision::Collidable::circle(diameter).with_layer(
+            collision::LAYER_ASTEROID,
+            collision::LAYER_PLAYER | collision::LAYER_PLAYER_BULLET | collision::LAYER_PLANET,
+        ))
+        // Heavier the bigger it is, so big ones hit harder. One 100 across weighs 100.
         .with(bounce::BounceOnCollision {
+            mass: diameter * diameter / 100.0,
             ..Default::default()
         })
+        .with(config.collisions.asteroid_impact.clone())
         .with(sprites::Sprite {
             shape: sprites::Shape::Asteroid,
-            scale: Point2::new(size, size),
+            scale: Point2::new(diameter, diameter),
             ..Default::default()
         })
         .with(despawn::DespawnBounds(Rect::new(
-            0.0 - HW - 200.0,
-            0.0 - HH - 200.0,
-            viewport::PLAYFIELD_WIDTH + 400.0,
-            viewport::PLAYFIELD_HEIGHT + 400.0,
+            0.0 - hw - 200.0,
+            0.0 - hh - 200.0,
+            playfield.width + 400.0,
+            playfield.height + 400.0,
         )))
         .with(health_damage::Health::new(100.0))
-        .with(score::PointsOnLastHit(1000))
+        .with(score::PointsOnLastHit(size.points(&config.asteroids)))
         .build();
 }
 
@@ -0,0 +1,934 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::Path;
+use specs::*;
+use ggez::{GameError, GameResult};
+use ggez::graphics::{Point2, Rect};
+use toml;
+use plugins::*;
+use SimulationControl;
+
+use super::{Asteroid, AsteroidSize, GameMode, GameModeManager, HeroPlanet, HeroPlayer};
+use super::mode_game_over::GameOverModeState;
+use super::mode_playing::PlayingModeState;
+use super::power_ups::{ActivePowerUps, PowerUp, PowerUpType};
+
+const FORMAT_VERSION: u32 = 3;
+pub const QUICKSAVE_FILENAME: &str = "quicksave.toml";
+
+pub struct SnapshotPlugin;
+impl Plugin for SnapshotPlugin {
+    fn name(&self) -> &'static str {
+        "snapshot"
+    }
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec![
+            "game",
+            "mode_playing",
+            "mode_game_over",
+            "rng",
+            "metadata",
+            "position_motion",
+            "simple_physics",
+            "bounce",
+            "thruster",
+            "guns",
+            "collision",
+            "health_damage",
+            "despawn",
+            "score",
+            "sprites",
+            "player_control",
+            "power_ups",
+        ]
+    }
+}
+
+// Writes the whole world out to `path`.
+pub fn save<P: AsRef<Path>>(world: &mut World, path: P) -> GameResult<()> {
+    let text = to_toml(world)?;
+    if let Some(dir) = path.as_ref().parent() {
+        fs::create_dir_all(dir)?;
+    }
+    File::create(path)?.write_all(text.as_bytes())?;
+    Ok(())
+}
+
+// Replaces every entity in the world with the ones saved in `path`.
+pub fn load<P: AsRef<Path>>(world: &mut World, path: P) -> GameResult<()> {
+    let mut text = String::new();
+    File::open(path)?.read_to_string(&mut text)?;
+    from_toml(world, &text)
+}
+
+pub fn to_toml(world: &World) -> GameResult<String> {
+    toml::to_string(&Snapshot::capture(world)).map_err(|e| snapshot_error(&e.to_string()))
+}
+
+pub fn from_toml(world: &mut World, text: &str) -> GameResult<()> {
+    let snapshot: Snapshot = toml::from_str(text).map_err(|e| snapshot_error(&e.to_string()))?;
+    snapshot.restore(world)
+}
+
+// Everything needed to pick a game up where it left off. Entities are numbered in the order
+// they're saved, and references between them use those numbers.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub format_version: u32,
+    pub version: String,
+    // u64s don't fit in TOML integers, so these are strings.
+    pub seed: String,
+    pub gameplay_rng: String,
+    pub cosmetic_rng: String,
+    pub modes: Vec<GameMode>,
+    pub score: i32,
+    pub displayed_score: i32,
+    pub ready_delay: f32,
+    #[serde(default)]
+    pub power_ups: Vec<PowerUpSnapshot>,
+    // Scores kept for slots other than the player's.
+    #[serde(default)]
+    pub scores: Vec<ScoreSnapshot>,
+    // TOML can't write a plain value or array after a table, so tables go last. How far through
+    // its tally and countdown game over is.
+    #[serde(default)]
+    pub game_over: GameOverModeState,
+    pub entities: Vec<EntitySnapshot>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PowerUpSnapshot {
+    pub power_up: PowerUpType,
+    pub remaining: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ScoreSnapshot {
+    pub slot: usize,
+    pub score: i32,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EntitySnapshot {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "is_false")]
+    pub hero_player: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub hero_planet: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asteroid_size: Option<AsteroidSize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub power_up: Option<PowerUpType>,
+    #[serde(skip_serializing_if = "is_false")]
+    pub player_control: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scores_points: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<usize>,
+    #[serde(skip_serializing_if = "is_false")]
+    pub despawn_on_collision: bool,
+    #[serde(skip_serializing_if = "is_false")]
+    pub fast_mover: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub friction: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speed_limit: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bounce_mass: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bounce_restitution: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collision_layer: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collision_mask: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub points_on_last_hit: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<f32>,
+    // TOML needs every plain value before the first table, so tables go below here.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<MotionSnapshot>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_position: Option<MotionSnapshot>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub velocity: Option<MotionSnapshot>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position_bounds: Option<RectSnapshot>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub despawn_bounds: Option<RectSnapshot>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collidable: Option<CollidableSnapshot>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health: Option<HealthSnapshot>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub damage_on_collision: Option<DamageOnCollisionSnapshot>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub impact_damage: Option<bounce::ImpactDamage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gun: Option<GunSnapshot>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sprite: Option<SpriteSnapshot>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thrusters: Option<BTreeMap<String, ThrusterSnapshot>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MotionSnapshot {
+    pub x: f32,
+    pub y: f32,
+    pub r: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RectSnapshot {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+impl RectSnapshot {
+    fn from_rect(rect: &Rect) -> RectSnapshot {
+        RectSnapshot {
+            x: rect.x,
+            y: rect.y,
+            w: rect.w,
+            h: rect.h,
+        }
+    }
+    fn to_rect(&self) -> Rect {
+        Rect::new(self.x, self.y, self.w, self.h)
+    }
+}
+
+// TOML can't hold arrays of tuples, so polygon points are [x, y] arrays.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "snake_case")]
+pub enum CollidableSnapshot {
+    Circle { radius: f32 },
+    Aabb { half_width: f32, half_height: f32 },
+    OrientedBox { half_width: f32, half_height: f32 },
+    Capsule { half_length: f32, radius: f32 },
+    Polygon { points: Vec<Vec<f32>> },
+}
+impl CollidableSnapshot {
+    fn from_shape(shape: &collision::CollisionShape) -> CollidableSnapshot {
+        use self::collision::CollisionShape::*;
+        match *shape {
+            Circle { radius } => CollidableSnapshot::Circle { radius },
+            Aabb {
+                half_width,
+                half_height,
+            } => CollidableSnapshot::Aabb {
+                half_width,
+                half_height,
+            },
+            OrientedBox {
+                half_width,
+                half_height,
+            } => CollidableSnapshot::OrientedBox {
+                half_width,
+                half_height,
+            },
+            Capsule {
+                half_length,
+                radius,
+            } => CollidableSnapshot::Capsule {
+                half_length,
+                radius,
+            },
+            Polygon { ref points } => CollidableSnapshot::Polygon {
+                points: points.iter().map(|&(x, y)| vec![x, y]).collect(),
+            },
+        }
+    }
+    fn to_shape(&self) -> collision::CollisionShape {
+        use self::collision::CollisionShape;
+        match *self {
+            CollidableSnapshot::Circle { radius } => CollisionShape::Circle { radius },
+            CollidableSnapshot::Aabb {
+                half_width,
+                half_height,
+            } => CollisionShape::Aabb {
+                half_width,
+                half_height,
+            },
+            CollidableSnapshot::OrientedBox {
+                half_width,
+                half_height,
+            } => CollisionShape::OrientedBox {
+                half_width,
+                half_height,
+            },
+            CollidableSnapshot::Capsule {
+                half_length,
+                radius,
+            } => CollisionShape::Capsule {
+                half_length,
+                radius,
+            },
+            CollidableSnapshot::Polygon { ref points } => CollisionShape::Polygon {
+                points: points.iter().map(|point| (point[0], point[1])).collect(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HealthSnapshot {
+    pub health: f32,
+    pub max_health: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_hurt_by: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_healed_by: Option<usize>,
+    #[serde(default)]
+    pub attackers: Vec<AttackerSnapshot>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AttackerSnapshot {
+    pub entity: usize,
+    pub damage: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DamageOnCollisionSnapshot {
+    pub damage: f32,
+    pub despawn: bool,
+    #[serde(default)]
+    pub exclude: Vec<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GunSnapshot {
+    pub firing: bool,
+    pub selected: usize,
+    pub period_scale: f32,
+    pub extra_spread: usize,
+    pub weapons: Vec<WeaponSnapshot>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WeaponSnapshot {
+    pub cooldown: f32,
+    pub heat: f32,
+    pub overheated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ammo: Option<u32>,
+    pub burst_left: usize,
+    pub def: guns::WeaponDef,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpriteSnapshot {
+    pub shape: sprites::Shape,
+    pub scale: PointSnapshot,
+    pub offset: PointSnapshot,
+}
+
+// TOML has no tuples, so points are tables.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PointSnapshot {
+    pub x: f32,
+    pub y: f32,
+}
+impl PointSnapshot {
+    fn from_point(point: &Point2) -> PointSnapshot {
+        PointSnapshot {
+            x: point.x,
+            y: point.y,
+        }
+    }
+    fn to_point(&self) -> Point2 {
+        Point2::new(self.x, self.y)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ThrusterSnapshot {
+    pub thrust: f32,
+    pub throttle: f32,
+    pub angle: f32,
+}
+
+impl Snapshot {
+    pub fn capture(world: &World) -> Snapshot {
+        let entities = world.entities();
+        let ids: HashMap<Entity, usize> = entities
+            .join()
+            .enumerate()
+            .map(|(id, entity)| (entity, id))
+            .collect();
+        let reference = |entity: &Entity| ids.get(entity).cloned();
+
+        let names = world.read::<metadata::Name>();
+        let tags = world.read::<metadata::Tags>();
+        let hero_players = world.read::<HeroPlayer>();
+        let hero_planets = world.read::<HeroPlanet>();
+        let asteroids = world.read::<Asteroid>();
+        let power_ups = world.read::<PowerUp>();
+        let player_controls = world.read::<player_control::PlayerControl>();
+        let scorers = world.read::<score::ScoresPoints>();
+        let owners = world.read::<health_damage::Owner>();
+        let despawn_on_collisions = world.read::<despawn::DespawnOnCollision>();
+        let fast_movers = world.read::<collision::FastMover>();
+        let frictions = world.read::<simple_physics::Friction>();
+        let speed_limits = world.read::<simple_physics::SpeedLimit>();
+        let bounces = world.read::<bounce::BounceOnCollision>();
+        let collidables = world.read::<collision::Collidable>();
+        let points = world.read::<score::PointsOnLastHit>();
+        let timeouts = world.read::<despawn::Timeout>();
+        let positions = world.read::<position_motion::Position>();
+        let previous_positions = world.read::<position_motion::PreviousPosition>();
+        let velocities = world.read::<position_motion::Velocity>();
+        let position_bounds = world.read::<position_motion::PositionBounds>();
+        let despawn_bounds = world.read::<despawn::DespawnBounds>();
+        let healths = world.read::<health_damage::Health>();
+        let damage_on_collisions = world.read::<health_damage::DamageOnCollision>();
+        let impact_damages = world.read::<bounce::ImpactDamage>();
+        let guns = world.read::<guns::Gun>();
+        let sprites = world.read::<sprites::Sprite>();
+        let thruster_sets = world.read::<thruster::ThrusterSet>();
+
+        let mut saved = Vec::new();
+        for entity in entities.join() {
+            saved.push(EntitySnapshot {
+                name: names.get(entity).map(|name| name.0.to_owned()),
+                tags: tags
+                    .get(entity)
+                    .map(|tags| tags.iter().map(|tag| (*tag).to_owned()).collect()),
+                hero_player: hero_players.get(entity).is_some(),
+                hero_planet: hero_planets.get(entity).is_some(),
+                asteroid_size: asteroids.get(entity).map(|asteroid| asteroid.0),
+                power_up: power_ups.get(entity).map(|power_up| power_up.0),
+                player_control: player_controls.get(entity).is_some(),
+                scores_points: scorers.get(entity).map(|scorer| scorer.0),
+                owner: owners.get(entity).and_then(|owner| reference(&owner.0)),
+                despawn_on_collision: despawn_on_collisions.get(entity).is_some(),
+                fast_mover: fast_movers.get(entity).is_some(),
+                friction: frictions.get(entity).map(|friction| friction.0),
+                speed_limit: speed_limits.get(entity).map(|limit| limit.0),
+                bounce_mass: bounces.get(entity).map(|bounce| bounce.mass),
+                bounce_restitution: bounces.get(entity).map(|bounce| bounce.restitution),
+                collision_layer: collidables.get(entity).map(|collidable| collidable.layer),
+                collision_mask: collidables.get(entity).map(|collidable| collidable.mask),
+                points_on_last_hit: points.get(entity).map(|points| points.0),
+                timeout: timeouts.get(entity).map(|timeout| timeout.0),
+                position: positions.get(entity).map(|p| MotionSnapshot {
+                    x: p.x,
+                    y: p.y,
+                    r: p.r,
+                }),
+                previous_position: previous_positions.get(entity).map(|p| MotionSnapshot {
+                    x: p.x,
+                    y: p.y,
+                    r: p.r,
+                }),
+                velocity: velocities.get(entity).map(|v| MotionSnapshot {
+                    x: v.x,
+                    y: v.y,
+                    r: v.r,
+                }),
+                position_bounds: position_bounds
+                    .get(entity)
+                    .map(|bounds| RectSnapshot::from_rect(&bounds.0)),
+                despawn_bounds: despawn_bounds
+                    .get(entity)
+                    .map(|bounds| RectSnapshot::from_rect(&bounds.0)),
+                collidable: collidables
+                    .get(entity)
+                    .map(|collidable| CollidableSnapshot::from_shape(&collidable.shape)),
+                health: healths.get(entity).map(|health| HealthSnapshot {
+                    health: health.health,
+                    max_health: health.max_health,
+                    last_hurt_by: health.last_hurt_by.as_ref().and_then(&reference),
+                    last_healed_by: health.last_healed_by.as_ref().and_then(&reference),
+                    // Anyone gone since can't be credited anyway.
+                    attackers: health
+                        .attackers
+                        .iter()
+                        .filter_map(|&(attacker, damage)| {
+                            reference(&attacker).map(|entity| AttackerSnapshot { entity, damage })
+                        })
+                        .collect(),
+                }),
+                damage_on_collision: damage_on_collisions.get(entity).map(|damage| {
+                    DamageOnCollisionSnapshot {
+                        damage: damage.damage,
+                        despawn: damage.despawn,
+                        exclude: damage.exclude.iter().filter_map(&reference).collect(),
+                    }
+                }),
+                impact_damage: impact_damages.get(entity).cloned(),
+                gun: guns.get(entity).map(|gun| GunSnapshot {
+                    firing: gun.firing,
+                    selected: gun.selected,
+                    period_scale: gun.period_scale,
+                    extra_spread: gun.extra_spread,
+                    weapons: gun
+                        .weapons
+                        .iter()
+                        .map(|weapon| WeaponSnapshot {
+                            cooldown: weapon.cooldown,
+                            heat: weapon.heat,
+                            overheated: weapon.overheated,
+                            ammo: weapon.ammo,
+                            burst_left: weapon.burst_left,
+                            def: weapon.def.clone(),
+                        })
+                        .collect(),
+                }),
+                sprite: sprites.get(entity).map(|sprite| SpriteSnapshot {
+                    shape: sprite.shape,
+                    scale: PointSnapshot::from_point(&sprite.scale),
+                    offset: PointSnapshot::from_point(&sprite.offset),
+                }),
+                thrusters: thruster_sets.get(entity).map(|set| {
+                    set.0
+                        .iter()
+                        .map(|(name, thruster)| {
+                            (
+                                (*name).to_owned(),
+                                ThrusterSnapshot {
+                                    thrust: thruster.thrust,
+                                    throttle: thruster.throttle,
+                                    angle: thruster.angle,
+                                },
+                            )
+                        })
+                        .collect()
+                }),
+            });
+        }
+
+        let rng = world.read_resource::<rng::GameRng>();
+        let player_score = world.read_resource::<score::PlayerScore>();
+        let game_mode = world.read_resource::<GameModeManager>();
+        Snapshot {
+            format_version: FORMAT_VERSION,
+            version: String::from(replay::BUILD_VERSION),
+            seed: rng.seed.to_string(),
+            gameplay_rng: rng.gameplay.state().to_string(),
+            cosmetic_rng: rng.cosmetic.state().to_string(),
+            modes: game_mode.stack.clone(),
+            score: player_score.get(),
+            displayed_score: player_score.get_displayed(),
+            ready_delay: world.read_resource::<PlayingModeState>().ready_delay,
+            power_ups: world
+                .read_resource::<ActivePowerUps>()
+                .0
+                .iter()
+                .map(|(&power_up, &remaining)| PowerUpSnapshot {
+                    power_up,
+                    remaining,
+                })
+                .collect(),
+            scores: world
+                .read_resource::<score::Scores>()
+                .iter()
+                .map(|(&slot, &score)| ScoreSnapshot { slot, score })
+                .collect(),
+            game_over: world.read_resource::<GameOverModeState>().clone(),
+            entities: saved,
+        }
+    }
+
+    // Deletes every entity in the world and recreates the saved ones. Modes are put back as they
+    // were without being entered again, so nothing gets reset.
+    pub fn restore(&self, world: &mut World) -> GameResult<()> {
+        if self.format_version != FORMAT_VERSION {
+            return Err(snapshot_error(&format!(
+                "unsupported snapshot format version {}",
+                self.format_version
+            )));
+        }
+        let seed = parse_u64("seed", &self.seed)?;
+        let gameplay_rng = parse_u64("gameplay_rng", &self.gameplay_rng)?;
+        let cosmetic_rng = parse_u64("cosmetic_rng", &self.cosmetic_rng)?;
+        let entity_count = self.entities.len();
+        let reference = |id: usize| -> GameResult<usize> {
+            if id < entity_count {
+                Ok(id)
+            } else {
+                Err(snapshot_error(&format!("no entity numbered {}", id)))
+            }
+        };
+        for saved in &self.entities {
+            if let Some(ref health) = saved.health {
+                for id in health
+                    .last_hurt_by
+                    .iter()
+                    .chain(health.last_healed_by.iter())
+                    .chain(health.attackers.iter().map(|attacker| &attacker.entity))
+                {
+                    reference(*id)?;
+                }
+            }
+            if let Some(id) = saved.owner {
+                reference(id)?;
+            }
+            if let Some(ref damage) = saved.damage_on_collision {
+                for id in &damage.exclude {
+                    reference(*id)?;
+                }
+            }
+            if let Some(CollidableSnapshot::Polygon { ref points }) = saved.collidable {
+                if points.iter().any(|point| point.len() != 2) {
+                    return Err(snapshot_error("polygon points should be [x, y] pairs"));
+                }
+            }
+        }
+
+        {
+            let entities = world.entities();
+            for entity in entities.join() {
+                if let Err(e) = entities.delete(entity) {
+                    println!("Error deleting entity: {:?}", e);
+                }
+            }
+        }
+        world.maintain();
+
+        let created: Vec<Entity> = (0..entity_count)
+            .map(|_| world.create_entity().build())
+            .collect();
+        for (saved, &entity) in self.entities.iter().zip(&created) {
+            saved.restore(world, entity, &created);
+        }
+
+        {
+            let mut rng = world.write_resource::<rng::GameRng>();
+            rng.seed = seed;
+            rng.gameplay = rng::RngStream::from_state(gameplay_rng);
+            rng.cosmetic = rng::RngStream::from_state(cosmetic_rng);
+        }
+        {
+            let mut player_score = world.write_resource::<score::PlayerScore>();
+            player_score.set(self.score);
+            player_score.set_displayed(self.displayed_score);
+        }
+        world.write_resource::<PlayingModeState>().ready_delay = self.ready_delay;
+        *world.write_resource::<GameOverModeState>() = self.game_over.clone();
+        world.write_resource::<ActivePowerUps>().0 = self
+            .power_ups
+            .iter()
+            .map(|saved| (saved.power_up, saved.remaining))
+            .collect();
+        {
+            let mut scores = world.write_resource::<score::Scores>();
+            scores.reset();
+            for saved in &self.scores {
+                scores.set(saved.slot, saved.score);
+            }
+        }
+        world
+            .write_resource::<GameModeManager>()
+            .restore(self.modes.clone());
+        world.write_resource::<SimulationControl>().paused = self.modes.contains(&GameMode::Paused);
+        world.write_resource::<player_control::Inputs>().reset();
+
+        // Anything still pointing at the entities that were just deleted would otherwise be
+        // acted on next tick, e.g. collisions ending between entities that no longer exist.
+        world.write_resource::<collision::Contacts>().0.clear();
+        world.write_resource::<collision::Collisions>().0.clear();
+        world.write_resource::<collision::ContactEvents>().clear();
+        world
+            .write_resource::<health_damage::DamageEventQueue>()
+            .clear();
+        world.write_resource::<despawn::DespawnEventQueue>().clear();
+        Ok(())
+    }
+}
+
+impl EntitySnapshot {
+    fn restore(&self, world: &mut World, entity: Entity, created: &[Entity]) {
+        if let Some(ref name) = self.name {
+            let name = world.write_resource::<metadata::Strings>().intern(name);
+            insert(world, entity, metadata::Name(name));
+        }
+        if let Some(ref tags) = self.tags {
+            let tags = {
+                let mut strings = world.write_resource::<metadata::Strings>();
+                tags.iter().map(|tag| strings.intern(tag)).collect()
+            };
+            insert(world, entity, metadata::Tags::new(tags));
+        }
+        if self.hero_player {
+            insert(world, entity, HeroPlayer);
+        }
+        if self.hero_planet {
+            insert(world, entity, HeroPlanet);
+        }
+        if let Some(size) = self.asteroid_size {
+            insert(world, entity, Asteroid(size));
+        }
+        if let Some(power_up) = self.power_up {
+            insert(world, entity, PowerUp(power_up));
+        }
+        if self.player_control {
+            insert(world, entity, player_control::PlayerControl);
+        }
+        if let Some(slot) = self.scores_points {
+            insert(world, entity, score::ScoresPoints(slot));
+        }
+        if let Some(id) = self.owner {
+            insert(world, entity, health_damage::Owner(created[id]));
+        }
+        if self.despawn_on_collision {
+            insert(world, entity, despawn::DespawnOnCollision);
+        }
+        if self.fast_mover {
+            insert(world, entity, collision::FastMover);
+        }
+        if let Some(friction) = self.friction {
+            insert(world, entity, simple_physics::Friction(friction));
+        }
+        if let Some(limit) = self.speed_limit {
+            insert(world, entity, simple_physics::SpeedLimit(limit));
+        }
+        if let Some(mass) = self.bounce_mass {
+            let restitution = self
+                .bounce_restitution
+                .unwrap_or(bounce::DEFAULT_RESTITUTION);
+            insert(
+                world,
+                entity,
+                bounce::BounceOnCollision { mass, restitution },
+            );
+        }
+        if let Some(points) = self.points_on_last_hit {
+            insert(world, entity, score::PointsOnLastHit(points));
+        }
+        if let Some(timeout) = self.timeout {
+            insert(world, entity, despawn::Timeout(timeout));
+        }
+        if let Some(ref p) = self.position {
+            insert(
+                world,
+                entity,
+                position_motion::Position {
+                    x: p.x,
+                    y: p.y,
+                    r: p.r,
+                },
+            );
+        }
+        if let Some(ref p) = self.previous_position {
+            insert(
+                world,
+                entity,
+                position_motion::PreviousPosition {
+                    x: p.x,
+                    y: p.y,
+                    r: p.r,
+                },
+            );
+        }
+        if let Some(ref v) = self.velocity {
+            insert(
+                world,
+                entity,
+                position_motion::Velocity {
+                    x: v.x,
+                    y: v.y,
+                    r: v.r,
+                },
+            );
+        }
+        if let Some(ref bounds) = self.position_bounds {
+            insert(
+                world,
+                entity,
+                position_motion::PositionBounds(bounds.to_rect()),
+            );
+        }
+        if let Some(ref bounds) = self.despawn_bounds {
+            insert(world, entity, despawn::DespawnBounds(bounds.to_rect()));
+        }
+        if let Some(ref collidable) = self.collidable {
+            let layer = self.collision_layer.unwrap_or(collision::LAYER_DEFAULT);
+            let mask = self.collision_mask.unwrap_or(collision::LAYER_ALL);
+            insert(
+                world,
+                entity,
+                collision::Collidable::new(collidable.to_shape()).with_layer(layer, mask),
+            );
+        }
+        if let Some(ref health) = self.health {
+            insert(
+                world,
+                entity,
+                health_damage::Health {
+                    health: health.health,
+                    max_health: health.max_health,
+                    last_hurt_by: health.last_hurt_by.map(|id| created[id]),
+                    last_healed_by: health.last_healed_by.map(|id| created[id]),
+                    attackers: health
+                        .attackers
+                        .iter()
+                        .map(|attacker| (created[attacker.entity], attacker.damage))
+                        .collect(),
+                },
+            );
+        }
+        if let Some(ref damage) = self.damage_on_collision {
+            insert(
+                world,
+                entity,
+                health_damage::DamageOnCollision {
+                    damage: damage.damage,
+                    despawn: damage.despawn,
+                    exclude: damage.exclude.iter().map(|&id| created[id]).collect(),
+                },
+            );
+        }
+        if let Some(ref impact_damage) = self.impact_damage {
+            insert(world, entity, impact_damage.clone());
+        }
+        if let Some(ref gun) = self.gun {
+            insert(
+                world,
+                entity,
+                guns::Gun {
+                    firing: gun.firing,
+                    selected: gun.selected,
+                    period_scale: gun.period_scale,
+                    extra_spread: gun.extra_spread,
+                    weapons: gun
+                        .weapons
+                        .iter()
+                        .map(|weapon| guns::Weapon {
+                            def: weapon.def.clone(),
+                            cooldown: weapon.cooldown,
+                            heat: weapon.heat,
+                            overheated: weapon.overheated,
+                            ammo: weapon.ammo,
+                            burst_left: weapon.burst_left,
+                        })
+                        .collect(),
+                },
+            );
+        }
+        if let Some(ref sprite) = self.sprite {
+            insert(
+                world,
+                entity,
+                sprites::Sprite {
+                    shape: sprite.shape,
+                    scale: sprite.scale.to_point(),
+                    offset: sprite.offset.to_point(),
+                },
+            );
+        }
+        if let Some(ref thrusters) = self.thrusters {
+            let thrusters = {
+                let mut strings = world.write_resource::<metadata::Strings>();
+                thrusters
+                    .iter()
+                    .map(|(name, thruster)| {
+                        (
+                            strings.intern(name),
+                            thruster::Thruster {
+                                thrust: thruster.thrust,
+                                throttle: thruster.throttle,
+                                angle: thruster.angle,
+                            },
+                        )
+                    })
+                    .collect()
+            };
+            insert(world, entity, thruster::ThrusterSet(thrusters));
+        }
+    }
+}
+
+fn insert<C: Component>(world: &mut World, entity: Entity, component: C) {
+    world.write::<C>().insert(entity, component);
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+fn parse_u64(key: &str, value: &str) -> GameResult<u64> {
+    value
+        .parse()
+        .map_err(|_| snapshot_error(&format!("{} should be a number, got {}", key, value)))
+}
+
+fn snapshot_error(message: &str) -> GameError {
+    GameError::ResourceLoadError(format!("Bad snapshot: {}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::GameConfig;
+    use game::mode_game_over::GameOverPhase;
+    use simulation::{Simulation, FIXED_STEP};
+
+    fn simulation<'a, 'b>() -> Simulation<'a, 'b> {
+        Simulation::new(::game_plugins(7, GameConfig::default())).unwrap()
+    }
+
+    fn change_mode(simulation: &mut Simulation, mode: GameMode) {
+        simulation
+            .world
+            .write_resource::<GameModeManager>()
+            .change(mode);
+        simulation.run_for(0.5).unwrap();
+    }
+
+    #[test]
+    fn round_trips_through_toml() {
+        let mut original = simulation();
+        change_mode(&mut original, GameMode::Playing);
+        original.run_for(3.0).unwrap();
+        change_mode(&mut original, GameMode::GameOver);
+        {
+            let mut game_over = original.world.write_resource::<GameOverModeState>();
+            game_over.phase = GameOverPhase::Countdown;
+            game_over.reset_ttl = 4.5;
+        }
+        let text = to_toml(&original.world).unwrap();
+
+        // Settled into attract mode first, the way playback restores a snapshot.
+        let mut restored = simulation();
+        restored.step(FIXED_STEP).unwrap();
+        from_toml(&mut restored.world, &text).unwrap();
+
+        assert_eq!(to_toml(&restored.world).unwrap(), text);
+        assert!(restored
+            .world
+            .read_resource::<GameModeManager>()
+            .is_current(GameMode::GameOver));
+        let game_over = restored.world.read_resource::<GameOverModeState>();
+        assert_eq!(game_over.phase, GameOverPhase::Countdown);
+        assert_eq!(game_over.reset_ttl, 4.5);
+    }
+
+    #[test]
+    fn keeps_collision_layers() {
+        let mut original = simulation();
+        change_mode(&mut original, GameMode::Playing);
+        let text = to_toml(&original.world).unwrap();
+
+        let mut restored = simulation();
+        restored.step(FIXED_STEP).unwrap();
+        from_toml(&mut restored.world, &text).unwrap();
+
+        let collidables = restored.world.read::<collision::Collidable>();
+        let hero_players = restored.world.read::<HeroPlayer>();
+        let (player, _) = (&collidables, &hero_players).join().next().unwrap();
+        assert_eq!(player.layer, collision::LAYER_PLAYER);
+        assert_ne!(player.mask, collision::LAYER_ALL);
+        let hero_planets = restored.world.read::<HeroPlanet>();
+        let (planet, _) = (&collidables, &hero_planets).join().next().unwrap();
+        assert_eq!(planet.layer, collision::LAYER_PLANET);
+    }
+}
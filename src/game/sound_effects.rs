@@ -1,14 +1,25 @@
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use specs::*;
 use ggez::*;
 use ggez::audio;
+use plugins::Plugin;
+use plugins::events::EventChannel;
+use plugins::rng::GameRng;
 
-pub fn init<'a, 'b>(
-    world: &mut World,
-    dispatcher: DispatcherBuilder<'a, 'b>,
-) -> DispatcherBuilder<'a, 'b> {
-    world.add_resource(SoundEffectQueue::new());
-    dispatcher
+pub struct SoundEffectsPlugin;
+impl Plugin for SoundEffectsPlugin {
+    fn name(&self) -> &'static str {
+        "sound_effects"
+    }
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec!["rng"]
+    }
+    fn setup(&self, world: &mut World) {
+        let mut queue = SoundEffectQueue::new();
+        queue.register_reader("sound_effects");
+        world.add_resource(queue);
+        world.add_resource(SoundSettings::default());
+    }
 }
 
 pub fn play(
@@ -16,38 +27,38 @@ pub fn play(
     ctx: &mut Context,
     sound_effects: &mut SoundEffects,
 ) -> GameResult<()> {
-    let mut queue = world.write_resource::<SoundEffectQueue>();
+    let queue = world.read_resource::<SoundEffectQueue>();
+    let settings = world.read_resource::<SoundSettings>();
+    let mut rng = world.write_resource::<GameRng>();
     sound_effects.maintain();
-    queue.execute(sound_effects, ctx)
+    // Read even while muted, so unmuting doesn't play a backlog of sounds.
+    for event in queue.read("sound_effects") {
+        if !settings.muted {
+            sound_effects.play(ctx, &event.effect_type, &mut rng.cosmetic)?;
+        }
+    }
+    Ok(())
 }
 
 pub struct SoundEffectEvent {
     effect_type: SoundEffectType,
 }
 
-pub struct SoundEffectQueue(pub Vec<SoundEffectEvent>);
-impl SoundEffectQueue {
-    pub fn new() -> SoundEffectQueue {
-        SoundEffectQueue(Vec::new())
-    }
+pub type SoundEffectQueue = EventChannel<SoundEffectEvent>;
+
+impl EventChannel<SoundEffectEvent> {
     pub fn play(&mut self, effect_type: SoundEffectType) -> &mut Self {
-        self.0.push(SoundEffectEvent { effect_type });
+        self.single_write(SoundEffectEvent { effect_type });
         self
     }
-    pub fn execute(
-        &mut self,
-        sound_effects: &mut SoundEffects,
-        ctx: &mut Context,
-    ) -> GameResult<()> {
-        for ref event in &self.0 {
-            sound_effects.play(ctx, &event.effect_type)?;
-        }
-        self.0.clear();
-        Ok(())
-    }
 }
 
-#[derive(Debug)]
+#[derive(Default)]
+pub struct SoundSettings {
+    pub muted: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum SoundEffectType {
     Ready,
     GameOver,
@@ -122,8 +133,13 @@ impl SoundEffects {
             ],
         })
     }
-    pub fn play(&mut self, ctx: &mut Context, effect_type: &SoundEffectType) -> GameResult<()> {
-        let sound_data = thread_rng()
+    pub fn play<R: Rng>(
+        &mut self,
+        ctx: &mut Context,
+        effect_type: &SoundEffectType,
+        rng: &mut R,
+    ) -> GameResult<()> {
+        let sound_data = rng
             .choose(match *effect_type {
                 SoundEffectType::Ready => &self.ready,
                 SoundEffectType::GameOver => &self.game_over,
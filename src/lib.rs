@@ -2,29 +2,113 @@ extern crate ggez;
 #[macro_use]
 extern crate maplit;
 extern crate rand;
+extern crate rayon;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
 extern crate specs;
 #[macro_use]
 extern crate specs_derive;
+extern crate toml;
 
 use specs::*;
 use ggez::*;
 
+pub mod config;
 pub mod game;
 pub mod plugins;
+pub mod simulation;
+
+pub struct CorePlugin;
+impl plugins::Plugin for CorePlugin {
+    fn name(&self) -> &'static str {
+        "core"
+    }
+    fn setup(&self, world: &mut World) {
+        world.add_resource(DeltaTime(0.016));
+        world.add_resource(SimulationControl::new());
+    }
+}
 
-pub fn init<'a, 'b>(
-    world: &mut World,
-    dispatcher: DispatcherBuilder<'a, 'b>,
-) -> DispatcherBuilder<'a, 'b> {
-    world.add_resource(DeltaTime(0.016));
-    dispatcher
+// Everything needed to run the full game. The same seed and the same inputs play out the same
+// session.
+pub fn game_plugins(seed: u64, config: config::GameConfig) -> plugins::PluginSet {
+    use plugins::*;
+    PluginSet::new()
+        .with(CorePlugin)
+        .with(rng::RngPlugin::new(seed))
+        .with(viewport::ViewportPlugin)
+        .with(config::ConfigPlugin::new(config))
+        .with(metadata::MetadataPlugin)
+        .with(guns::GunsPlugin)
+        .with(thruster::ThrusterPlugin)
+        .with(collision::CollisionPlugin)
+        .with(bounce::BouncePlugin)
+        .with(health_damage::HealthDamagePlugin)
+        .with(player_control::PlayerControlPlugin)
+        .with(replay::ReplayPlugin)
+        .with(simple_physics::SimplePhysicsPlugin)
+        .with(position_motion::PositionMotionPlugin)
+        .with(sprites::SpritesPlugin)
+        .with(despawn::DespawnPlugin)
+        .with(score::ScorePlugin)
+        .with(game::GamePlugin)
+        .with(game::high_scores::HighScoresPlugin)
+        .with(game::sound_effects::SoundEffectsPlugin)
+        .with(game::power_ups::PowerUpsPlugin)
+        .with(game::mode_attract::AttractModePlugin)
+        .with(game::mode_playing::PlayingModePlugin)
+        .with(game::mode_game_over::GameOverModePlugin)
+        .with(game::mode_paused::PausedModePlugin)
+        .with(game::mode_enter_initials::EnterInitialsModePlugin)
+        .with(game::snapshot::SnapshotPlugin)
+}
+
+// Sets a freshly built simulation up to play back `recording`, from the world it was recorded
+// in if it didn't start fresh.
+pub fn start_playback(
+    simulation: &mut simulation::Simulation,
+    recording: plugins::replay::Recording,
+) -> GameResult<()> {
+    if !recording.start.is_empty() {
+        // Settle into a mode first, so that entering it doesn't wipe the snapshot.
+        simulation.step(simulation::FIXED_STEP)?;
+        game::snapshot::from_toml(&mut simulation.world, &recording.start)?;
+    }
+    plugins::replay::start_playback(&mut simulation.world, recording);
+    Ok(())
 }
 
 #[derive(Debug)]
 pub struct DeltaTime(pub f32);
 
-pub fn update_delta_time(world: &mut World, ctx: &mut Context) {
+// Requests from inside the world to whatever is running it.
+#[derive(Debug)]
+pub struct SimulationControl {
+    // Only input and game mode stages run while paused.
+    pub paused: bool,
+    pub quit: bool,
+}
+impl SimulationControl {
+    pub fn new() -> SimulationControl {
+        SimulationControl {
+            paused: false,
+            quit: false,
+        }
+    }
+}
+impl Default for SimulationControl {
+    fn default() -> SimulationControl {
+        SimulationControl::new()
+    }
+}
+
+pub fn get_delta_seconds(ctx: &Context) -> f32 {
     let dt = ggez::timer::get_delta(ctx);
+    dt.as_secs() as f32 + dt.subsec_nanos() as f32 * 1e-9
+}
+
+pub fn update_delta_time(world: &mut World, ctx: &mut Context) {
     let mut delta = world.write_resource::<DeltaTime>();
-    *delta = DeltaTime(dt.as_secs() as f32 + dt.subsec_nanos() as f32 * 1e-9);
+    *delta = DeltaTime(get_delta_seconds(ctx));
 }
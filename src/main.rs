@@ -3,15 +3,223 @@ extern crate invaders;
 extern crate rand;
 extern crate specs;
 
+mod cli;
+
 use std::env;
 use std::path;
+use std::process;
 use ggez::*;
 use ggez::event::*;
-use specs::*;
 use invaders::*;
+use invaders::game::GameModeManager;
 use invaders::plugins::*;
+use invaders::simulation::Simulation;
 
 pub fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match cli::parse(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("{}\n\n{}", e, cli::USAGE);
+            process::exit(2);
+        }
+    };
+    if options.help {
+        println!("{}", cli::USAGE);
+        return;
+    }
+
+    let result = if options.headless {
+        run_headless(&options)
+    } else {
+        run_windowed(&options)
+    };
+    if let Err(e) = result {
+        println!("Error: {}", e);
+        process::exit(1);
+    }
+}
+
+// Everything needed to start a session, shared by windowed and headless runs.
+struct Session {
+    seed: u64,
+    playback: Option<replay::Recording>,
+    config: config::GameConfig,
+}
+
+fn load_session(options: &cli::Options) -> GameResult<Session> {
+    // A replay brings its own seed.
+    let playback = match options.replay {
+        Some(ref path) => Some(replay::Recording::load(path)?),
+        None => None,
+    };
+    if let Some(ref recording) = playback {
+        if recording.version != replay::BUILD_VERSION {
+            println!(
+                "Replay was recorded with version {} - it may not play back the same",
+                recording.version
+            );
+        }
+    }
+    let seed = match playback {
+        Some(ref recording) => recording.seed,
+        None => options.seed.unwrap_or_else(rng::random_seed),
+    };
+    println!("Seed: {}", seed);
+
+    // Without --config, use config.toml from the resources directory if there is one.
+    let config_path = options.config.clone().or_else(|| {
+        resources_dir(options)
+            .map(|dir| dir.join("config.toml"))
+            .filter(|path| path.is_file())
+    });
+    let config = match config_path {
+        Some(path) => config::GameConfig::load(path, &options.overrides)?,
+        None => config::GameConfig::parse("", &options.overrides)?,
+    };
+    // A replay only plays back the same under the config it was recorded with.
+    let config = match playback {
+        Some(ref recording) => {
+            let recorded = config::GameConfig::parse(&recording.config, &[])?;
+            if recorded != config {
+                println!("Replay was recorded with a different config - using the replay's");
+            }
+            recorded
+        }
+        None => config,
+    };
+
+    Ok(Session {
+        seed,
+        playback,
+        config,
+    })
+}
+
+fn resources_dir(options: &cli::Options) -> Option<path::PathBuf> {
+    options.resources.clone().or_else(|| {
+        env::var("CARGO_MANIFEST_DIR").ok().map(|manifest_dir| {
+            let mut path = path::PathBuf::from(manifest_dir);
+            path.push("resources");
+            path
+        })
+    })
+}
+
+fn start_session(
+    simulation: &mut Simulation,
+    options: &cli::Options,
+    session: &mut Session,
+) -> GameResult<()> {
+    // The game starts in attract mode on its own.
+    let mut fresh = options.mode == game::GameMode::Attract && options.snapshot.is_none();
+    if let Some(recording) = session.playback.take() {
+        // The replay knows how its session started.
+        if !fresh {
+            println!("Ignoring --mode and --snapshot while playing back a replay");
+        }
+        fresh = recording.start.is_empty();
+        invaders::start_playback(simulation, recording)?;
+    } else if !fresh {
+        if options.mode != game::GameMode::Attract {
+            simulation
+                .world
+                .write_resource::<GameModeManager>()
+                .change(options.mode);
+        }
+        // Settle into the starting mode first, so that entering it doesn't wipe the snapshot.
+        simulation.step(simulation::FIXED_STEP)?;
+        if let Some(ref path) = options.snapshot {
+            game::snapshot::load(&mut simulation.world, path)?;
+        }
+    }
+    let world = &mut simulation.world;
+    if options.record.is_some() {
+        // A session that didn't start fresh can only be played back from where it started.
+        let start = if fresh {
+            String::new()
+        } else {
+            game::snapshot::to_toml(world)?
+        };
+        replay::start_recording(world, session.seed, session.config.to_toml()?, start);
+    }
+    // Asking for a profiler dump turns profiling on.
+    world.write_resource::<profiler::Profiler>().enabled =
+        options.profile || options.profile_csv.is_some() || options.profile_trace.is_some();
+    Ok(())
+}
+
+fn finish_session(simulation: &mut Simulation, options: &cli::Options) {
+    if let Some(ref path) = options.record {
+        match replay::save_recording(&mut simulation.world, path) {
+            Ok(()) => println!("Saved replay to {}", path.display()),
+            Err(e) => println!("Could not save replay {}: {}", path.display(), e),
+        }
+    }
+    let profiler = simulation.world.read_resource::<profiler::Profiler>();
+    if let Some(ref path) = options.profile_csv {
+        match profiler.write_csv(path) {
+            Ok(()) => println!("Saved profile to {}", path.display()),
+            Err(e) => println!("Could not save profile {}: {}", path.display(), e),
+        }
+    }
+    if let Some(ref path) = options.profile_trace {
+        match profiler.write_trace(path) {
+            Ok(()) => println!("Saved trace to {}", path.display()),
+            Err(e) => println!("Could not save trace {}: {}", path.display(), e),
+        }
+    }
+}
+
+fn run_headless(options: &cli::Options) -> GameResult<()> {
+    let mut session = load_session(options)?;
+    let mut simulation = Simulation::new(game_plugins(session.seed, session.config.clone()))?;
+    start_session(&mut simulation, options, &mut session)?;
+    for _ in 0..options.ticks {
+        simulation.step(simulation::FIXED_STEP)?;
+        if simulation.world.read_resource::<SimulationControl>().quit {
+            break;
+        }
+    }
+    finish_session(&mut simulation, options);
+
+    let player_score = simulation.world.read_resource::<score::PlayerScore>();
+    let game_mode = simulation.world.read_resource::<GameModeManager>();
+    println!(
+        "seed: {} ticks: {} elapsed: {:.2}s mode: {:?} score: {}",
+        session.seed,
+        simulation.ticks,
+        simulation.elapsed,
+        game_mode.current(),
+        player_score.get()
+    );
+    if options.profile {
+        println!(
+            "{:<28} {:>8} {:>8} {:>8} {:>8}",
+            "ms", "avg", "p50", "p95", "max"
+        );
+        for row in simulation
+            .world
+            .read_resource::<profiler::Profiler>()
+            .summary()
+        {
+            println!(
+                "{:<28} {:>8.4} {:>8.4} {:>8.4} {:>8.4}",
+                row.name, row.average, row.p50, row.p95, row.max
+            );
+        }
+    }
+    Ok(())
+}
+
+fn run_windowed(options: &cli::Options) -> GameResult<()> {
+    let mut session = load_session(options)?;
+
+    let fullscreen_type = if options.fullscreen {
+        conf::FullscreenType::Desktop
+    } else {
+        conf::FullscreenType::Off
+    };
     let mut cb = ContextBuilder::new("rustinvaders", "ggez")
         .window_setup(
             conf::WindowSetup::default()
@@ -20,46 +228,66 @@ pub fn main() {
                 .samples(4)
                 .unwrap(),
         )
-        .window_mode(conf::WindowMode::default().dimensions(800, 600));
+        .window_mode(
+            conf::WindowMode::default()
+                .dimensions(options.width, options.height)
+                .fullscreen_type(fullscreen_type),
+        );
 
-    if let Ok(manifest_dir) = env::var("CARGO_MANIFEST_DIR") {
-        let mut path = path::PathBuf::from(manifest_dir);
-        path.push("resources");
+    // Otherwise ggez looks for a resources directory next to the executable.
+    if let Some(path) = resources_dir(options) {
         println!("Adding path {:?}", path);
         cb = cb.add_resource_path(path);
-    } else {
-        println!("Not building from cargo?  Ok.");
     }
 
-    let ctx = &mut cb.build().unwrap();
+    let ctx = &mut cb.build()?;
 
     ctx.print_resource_stats();
 
-    match MainState::new(ctx) {
-        Err(e) => {
-            println!("Could not load game!");
-            println!("Error: {}", e);
-        }
-        Ok(ref mut state) => {
-            {
-                let (width, height) = graphics::get_size(ctx);
-                let mut viewport = state.world.write_resource::<viewport::ViewportState>();
-                viewport.update_screen(width as f32, height as f32);
-            }
-            event::run(ctx, state).unwrap();
+    let state = &mut MainState::new(ctx, session.seed, session.config.clone())?;
+    {
+        let (width, height) = graphics::get_size(ctx);
+        let mut viewport = state
+            .simulation
+            .world
+            .write_resource::<viewport::ViewportState>();
+        viewport.update_screen(width as f32, height as f32);
+    }
+    // A replay has to take the same path through the game wherever it's played, so the table
+    // is left empty - and untouched on disk - while recording or playing one back. Headless runs
+    // never load it either.
+    if session.playback.is_some() || options.record.is_some() {
+        println!("High scores are off while recording or playing back a replay");
+    } else {
+        let world = &mut state.simulation.world;
+        let high_scores_path = ctx
+            .filesystem
+            .get_user_config_dir()
+            .join(game::high_scores::FILENAME);
+        if let Err(e) = game::high_scores::load(world, &high_scores_path) {
+            println!("Could not load high scores: {}", e);
         }
     }
+    start_session(&mut state.simulation, options, &mut session)?;
+
+    event::run(ctx, state)?;
+
+    finish_session(&mut state.simulation, options);
+    Ok(())
 }
 
 pub struct MainState<'a, 'b> {
-    world: World,
-    dispatcher: Dispatcher<'a, 'b>,
+    simulation: simulation::Simulation<'a, 'b>,
     font: plugins::fonts::Font,
     sound_effects: game::sound_effects::SoundEffects,
 }
 
 impl<'a, 'b> MainState<'a, 'b> {
-    fn new(ctx: &mut Context) -> GameResult<MainState<'a, 'b>> {
+    fn new(
+        ctx: &mut Context,
+        seed: u64,
+        config: config::GameConfig,
+    ) -> GameResult<MainState<'a, 'b>> {
         let mut font = fonts::Font::new(&fonts::FUTURAL);
         if let Err(err) = font.load(ctx) {
             return Err(GameError::FontError(format!(
@@ -68,48 +296,29 @@ impl<'a, 'b> MainState<'a, 'b> {
             )));
         }
 
-        let mut world = World::new();
-
-        let mut dispatcher = DispatcherBuilder::new();
-        let init_funcs = [
-            init,
-            viewport::init,
-            metadata::init,
-            guns::init,
-            thruster::init,
-            collision::init,
-            bounce::init,
-            health_damage::init,
-            player_control::init,
-            simple_physics::init,
-            position_motion::init,
-            sprites::init,
-            despawn::init,
-            score::init,
-            game::init,
-        ];
-        for init_func in init_funcs.iter() {
-            dispatcher = init_func(&mut world, dispatcher);
-        }
+        let simulation = simulation::Simulation::new(game_plugins(seed, config))?;
 
         let sound_effects = game::sound_effects::SoundEffects::new(ctx)?;
 
         Ok(MainState {
             font,
-            world,
+            simulation,
             sound_effects,
-            dispatcher: dispatcher.build(),
         })
     }
 }
 
 impl<'a, 'b> event::EventHandler for MainState<'a, 'b> {
     fn update(&mut self, ctx: &mut Context) -> GameResult<()> {
-        update_delta_time(&mut self.world, ctx);
-        viewport::update(&mut self.world, ctx)?;
-        self.dispatcher.dispatch(&self.world.res);
-        despawn::update(&mut self.world)?;
-        self.world.maintain();
+        self.simulation.update(get_delta_seconds(ctx))?;
+        let quit = self
+            .simulation
+            .world
+            .read_resource::<SimulationControl>()
+            .quit;
+        if quit {
+            ctx.quit()?;
+        }
         Ok(())
     }
 
@@ -117,33 +326,47 @@ impl<'a, 'b> event::EventHandler for MainState<'a, 'b> {
         graphics::set_background_color(ctx, graphics::BLACK);
         graphics::clear(ctx);
         graphics::set_color(ctx, graphics::WHITE)?;
-        viewport::draw(&mut self.world, ctx)?;
-        sprites::draw(&mut self.world, ctx)?;
-        game::draw(
-            &mut self.world,
-            ctx,
-            &mut self.font,
-            &mut self.sound_effects,
-        )?;
+        let world = &mut self.simulation.world;
+        viewport::draw(world, ctx)?;
+
+        let start = profiler::start(world);
+        sprites::draw(world, ctx)?;
+        profiler::finish(world, "draw_sprites", start);
+
+        let start = profiler::start(world);
+        game::draw(world, ctx, &mut self.font, &mut self.sound_effects)?;
+        profiler::finish(world, "draw_game", start);
+
+        profiler::draw_overlay(world, &mut self.font, ctx)?;
+
+        let start = profiler::start(world);
         graphics::present(ctx);
+        profiler::finish(world, "present", start);
         timer::yield_now();
         Ok(())
     }
 
     fn resize_event(&mut self, _ctx: &mut Context, width: u32, height: u32) {
-        let mut viewport = self.world.write_resource::<viewport::ViewportState>();
+        let mut viewport = self
+            .simulation
+            .world
+            .write_resource::<viewport::ViewportState>();
         viewport.update_screen(width as f32, height as f32);
     }
 
     fn focus_event(&mut self, _ctx: &mut Context, gained: bool) {
-        if gained {
-        } else {
+        // Losing focus presses pause, unless already paused - it shouldn't unpause.
+        if !gained && !self.simulation.is_paused() {
+            self.simulation
+                .world
+                .write_resource::<player_control::Inputs>()
+                .pause = true;
         }
     }
 
     fn mouse_wheel_event(&mut self, _ctx: &mut Context, _x: i32, _y: i32) {
         /*
-        let mut viewport = self.world.write_resource::<viewport::ViewportState>();
+        let mut viewport = self.simulation.world.write_resource::<viewport::ViewportState>();
         if y < 0 {
             viewport.decrease_zoom(0.1);
         } else if y > 0 {
@@ -153,19 +376,61 @@ impl<'a, 'b> event::EventHandler for MainState<'a, 'b> {
     }
 
     fn key_down_event(&mut self, ctx: &mut Context, keycode: Keycode, keymod: Mod, repeat: bool) {
-        player_control::key_down_event(&mut self.world, ctx, keycode, keymod, repeat);
+        // F3 shows the profiler, F5 quicksaves, F9 picks up from the last quicksave.
+        let quicksave_path = ctx
+            .filesystem
+            .get_user_config_dir()
+            .join(game::snapshot::QUICKSAVE_FILENAME);
+        // A replay's inputs don't capture a world loaded halfway through, so it would play back
+        // differently.
+        let replaying =
+            self.simulation.world.read_resource::<replay::Replay>().mode != replay::ReplayMode::Off;
+        match keycode {
+            Keycode::F3 if !repeat => profiler::toggle_overlay(&mut self.simulation.world),
+            Keycode::F5 | Keycode::F9 if !repeat && replaying => {
+                println!("Quicksaves are off while recording or playing back a replay")
+            }
+            Keycode::F5 if !repeat => {
+                match game::snapshot::save(&mut self.simulation.world, &quicksave_path) {
+                    Ok(()) => println!("Saved snapshot to {}", quicksave_path.display()),
+                    Err(e) => println!("Could not save snapshot: {}", e),
+                }
+            }
+            Keycode::F9 if !repeat => {
+                if let Err(e) = game::snapshot::load(&mut self.simulation.world, &quicksave_path) {
+                    println!("Could not load snapshot: {}", e);
+                }
+            }
+            _ => player_control::key_down_event(
+                &mut self.simulation.world,
+                ctx,
+                keycode,
+                keymod,
+                repeat,
+            ),
+        }
     }
 
     fn key_up_event(&mut self, ctx: &mut Context, keycode: Keycode, keymod: Mod, repeat: bool) {
-        player_control::key_up_event(&mut self.world, ctx, keycode, keymod, repeat);
+        player_control::key_up_event(&mut self.simulation.world, ctx, keycode, keymod, repeat);
     }
 
     fn controller_button_down_event(&mut self, ctx: &mut Context, btn: Button, instance_id: i32) {
-        player_control::controller_button_down_event(&mut self.world, ctx, btn, instance_id);
+        player_control::controller_button_down_event(
+            &mut self.simulation.world,
+            ctx,
+            btn,
+            instance_id,
+        );
     }
 
     fn controller_button_up_event(&mut self, ctx: &mut Context, btn: Button, instance_id: i32) {
-        player_control::controller_button_up_event(&mut self.world, ctx, btn, instance_id);
+        player_control::controller_button_up_event(
+            &mut self.simulation.world,
+            ctx,
+            btn,
+            instance_id,
+        );
     }
 
     fn controller_axis_event(
@@ -175,6 +440,12 @@ impl<'a, 'b> event::EventHandler for MainState<'a, 'b> {
         value: i16,
         instance_id: i32,
     ) {
-        player_control::controller_axis_event(&mut self.world, ctx, axis, value, instance_id);
+        player_control::controller_axis_event(
+            &mut self.simulation.world,
+            ctx,
+            axis,
+            value,
+            instance_id,
+        );
     }
 }
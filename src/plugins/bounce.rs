@@ -1,131 +1,249 @@
 use specs::*;
 use plugins::*;
 
-pub fn init<'a, 'b>(
-    world: &mut World,
-    dispatcher: DispatcherBuilder<'a, 'b>,
-) -> DispatcherBuilder<'a, 'b> {
-    world.register::<BounceOnCollision>();
-    dispatcher.add(BounceOnCollisionSystem, "bounce_on_collision", &[])
+pub struct BouncePlugin;
+impl Plugin for BouncePlugin {
+    fn name(&self) -> &'static str {
+        "bounce"
+    }
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec!["position_motion", "collision", "health_damage"]
+    }
+    fn setup(&self, world: &mut World) {
+        world.register::<BounceOnCollision>();
+        world.register::<ImpactDamage>();
+    }
+    fn systems<'a, 'b>(&self, systems: &mut SystemRegistry<'a, 'b>) {
+        systems.add(
+            Stage::Collision,
+            BounceOnCollisionSystem,
+            "bounce_on_collision",
+            &["collision", "continuous_collision"],
+        );
+    }
 }
 
+// How much of their closing speed bodies keep when they bounce apart, unless they say otherwise.
+pub const DEFAULT_RESTITUTION: f32 = 1.0;
+// How much of the overlap is pushed out each tick, and how much is left alone so resting bodies
+// don't jitter.
+const CORRECTION_PERCENT: f32 = 0.8;
+const CORRECTION_SLOP: f32 = 0.5;
+// How hard bodies grip each other when they scrape past, and so how much spin they pick up.
+const FRICTION: f32 = 0.3;
+
 #[derive(Component, Debug)]
 pub struct BounceOnCollision {
     pub mass: f32,
+    // From 0.0, where bodies stop dead against each other, to 1.0, where they bounce off with
+    // all their speed. A pair bounces with the lower of the two.
+    pub restitution: f32,
 }
 impl Default for BounceOnCollision {
     fn default() -> BounceOnCollision {
-        BounceOnCollision { mass: 100.0 }
+        BounceOnCollision {
+            mass: 100.0,
+            restitution: DEFAULT_RESTITUTION,
+        }
+    }
+}
+
+// How much damage something takes from the energy of an impact, i.e. half the pair's reduced
+// mass times the square of their closing speed along the contact normal. That runs into the
+// hundreds of thousands for an asteroid hitting the planet, hence the exponent to tame it.
+// Damage is `scale * (energy - threshold) ^ exponent`, up to `max`.
+#[derive(Component, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImpactDamage {
+    // Bumps with less energy than this do nothing.
+    pub threshold: f32,
+    pub scale: f32,
+    pub exponent: f32,
+    pub max: f32,
+}
+impl ImpactDamage {
+    pub fn damage(&self, energy: f32) -> f32 {
+        let energy = energy - self.threshold;
+        if energy <= 0.0 {
+            return 0.0;
+        }
+        (self.scale * energy.powf(self.exponent)).min(self.max)
+    }
+}
+impl Default for ImpactDamage {
+    fn default() -> ImpactDamage {
+        ImpactDamage {
+            threshold: 0.0,
+            scale: 0.15,
+            exponent: 0.5,
+            max: 1000.0,
+        }
     }
 }
 
 pub struct BounceOnCollisionSystem;
 impl<'a> System<'a> for BounceOnCollisionSystem {
     type SystemData = (
-        Entities<'a>,
-        Fetch<'a, collision::Collisions>,
+        Fetch<'a, collision::Contacts>,
         FetchMut<'a, health_damage::DamageEventQueue>,
         ReadStorage<'a, BounceOnCollision>,
+        ReadStorage<'a, ImpactDamage>,
+        ReadStorage<'a, collision::Collidable>,
         WriteStorage<'a, position_motion::Position>,
         WriteStorage<'a, position_motion::Velocity>,
     );
     fn run(&mut self, data: Self::SystemData) {
-        let (entities, collisions, mut _damage, bounces, positions, mut vels) = data;
-
-        // TODO: Track seen entity pairs, skip those already processed
-        for (a_entity, a_bounce) in (&*entities, &bounces).join() {
-            if let Some(ent_collisions) = collisions.get(&a_entity) {
-                for b_entity in ent_collisions.iter() {
-                    // TODO: rework this code a bit with some more match / if let action?
-                    let result;
-                    {
-                        let a_position = positions.get(a_entity);
-                        let a_velocity = vels.get(a_entity);
-                        let b_bounce = bounces.get(*b_entity);
-                        let b_position = positions.get(*b_entity);
-                        let b_velocity = vels.get(*b_entity);
-
-                        if a_position.is_none() || a_velocity.is_none() || b_bounce.is_none()
-                            || b_position.is_none()
-                            || b_velocity.is_none()
-                        {
-                            continue;
-                        }
-
-                        result = resolve_elastic_collision(
-                            a_bounce,
-                            a_position.unwrap(),
-                            a_velocity.unwrap(),
-                            b_bounce.unwrap(),
-                            b_position.unwrap(),
-                            b_velocity.unwrap(),
-                        );
-                    }
-                    if let Some((ax, ay, bx, by)) = result {
-                        {
-                            let mut a_velocity = vels.get_mut(a_entity).unwrap();
-                            a_velocity.x = ax;
-                            a_velocity.y = ay;
-                        }
-                        {
-                            let mut b_velocity = vels.get_mut(*b_entity).unwrap();
-                            b_velocity.x = bx;
-                            b_velocity.y = by;
-                        }
-                    }
+        let (contacts, mut damages, bounces, impact_damages, collidables, mut positions, mut vels) =
+            data;
+
+        // Contacts holds each touching pair once, with the normal pointing from a to b.
+        for contact in contacts.0.values() {
+            let a = match body(contact.a, &bounces, &collidables, &vels) {
+                Some(a) => a,
+                None => continue,
+            };
+            let b = match body(contact.b, &bounces, &collidables, &vels) {
+                Some(b) => b,
+                None => continue,
+            };
+            let (a_moved, b_moved, energy) =
+                match resolve_collision(&a, &b, contact.normal, contact.depth) {
+                    Some(result) => result,
+                    None => continue,
+                };
+            for &(to, from) in &[(contact.a, contact.b), (contact.b, contact.a)] {
+                let amount = impact_damages
+                    .get(to)
+                    .map_or(0.0, |impact_damage| impact_damage.damage(energy));
+                // Leave alone who last hurt it, which scoring goes by, if this didn't hurt.
+                if amount > 0.0 {
+                    damages.hurt(from, to, amount);
+                }
+            }
+            for &(entity, ref moved) in &[(contact.a, a_moved), (contact.b, b_moved)] {
+                if let Some(position) = positions.get_mut(entity) {
+                    position.x += moved.push.0;
+                    position.y += moved.push.1;
+                }
+                if let Some(velocity) = vels.get_mut(entity) {
+                    velocity.x = moved.velocity.0;
+                    velocity.y = moved.velocity.1;
+                    velocity.r = moved.spin;
                 }
             }
         }
     }
 }
 
-fn rotate(vx: f32, vy: f32, angle: f32) -> (f32, f32) {
-    (
-        vx * angle.cos() - vy * angle.sin(),
-        vx * angle.sin() + vy * angle.cos(),
-    )
+// What the collision math needs to know about one side of a contact.
+struct Body {
+    inverse_mass: f32,
+    restitution: f32,
+    radius: f32,
+    velocity: (f32, f32),
+    spin: f32,
 }
 
-// Elastic collision math transliterated from JavaScript
-// https://github.com/lmorchard/panic-ranger/blob/master/src/plugins/bounce.js
-// https://gist.github.com/christopher4lis/f9ccb589ee8ecf751481f05a8e59b1dc
-fn resolve_elastic_collision(
-    a_bounce: &BounceOnCollision,
-    a_position: &position_motion::Position,
-    a_velocity: &position_motion::Velocity,
-    b_bounce: &BounceOnCollision,
-    b_position: &position_motion::Position,
-    b_velocity: &position_motion::Velocity,
-) -> Option<(f32, f32, f32, f32)> {
-    let x_velocity_diff = a_velocity.x - b_velocity.x;
-    let y_velocity_diff = a_velocity.y - b_velocity.y;
-
-    let x_dist = b_position.x - a_position.x;
-    let y_dist = b_position.y - a_position.y;
-
-    if x_velocity_diff * x_dist + y_velocity_diff * y_dist <= 0.0 {
-        // Prevent accidental overlap of particles
-        return None;
-    }
-
-    let angle = 0.0 - (b_position.y - a_position.y).atan2(b_position.x - a_position.x);
-
-    let m1 = a_bounce.mass;
-    let m2 = b_bounce.mass;
-
-    let (u1x, u1y) = rotate(a_velocity.x, a_velocity.y, angle);
-    let (u2x, u2y) = rotate(b_velocity.x, b_velocity.y, angle);
-
-    let v1x = u1x * (m1 - m2) / (m1 + m2) + u2x * 2.0 * m2 / (m1 + m2);
-    let v1y = u1y;
+fn body(
+    entity: Entity,
+    bounces: &ReadStorage<BounceOnCollision>,
+    collidables: &ReadStorage<collision::Collidable>,
+    vels: &WriteStorage<position_motion::Velocity>,
+) -> Option<Body> {
+    let bounce = bounces.get(entity)?;
+    let velocity = vels.get(entity)?;
+    Some(Body {
+        // Nothing massless can be pushed around, so treat it as immovable.
+        inverse_mass: if bounce.mass > 0.0 {
+            1.0 / bounce.mass
+        } else {
+            0.0
+        },
+        restitution: bounce.restitution,
+        radius: collidables
+            .get(entity)
+            .map_or(0.0, |collidable| collidable.size() / 2.0),
+        velocity: (velocity.x, velocity.y),
+        spin: velocity.r,
+    })
+}
 
-    let v2x = u2x * (m1 - m2) / (m1 + m2) + u1x * 2.0 * m2 / (m1 + m2);
-    let v2y = u2y;
+// Where one side of a contact ends up: how far to push it out, and its new velocity and spin.
+struct Resolved {
+    push: (f32, f32),
+    velocity: (f32, f32),
+    spin: f32,
+}
 
-    let (vf1x, vf1y) = rotate(v1x, v1y, 0.0 - angle);
-    let (vf2x, vf2y) = rotate(v2x, v2y, 0.0 - angle);
+// Impulse-based collision response, treating each body as a solid disc of its bounding size so
+// scraping past something sets it spinning. The normal points from a to b. Also returns the
+// energy of the impact, which is zero unless they were closing.
+fn resolve_collision(
+    a: &Body,
+    b: &Body,
+    normal: (f32, f32),
+    depth: f32,
+) -> Option<(Resolved, Resolved, f32)> {
+    let total_inverse_mass = a.inverse_mass + b.inverse_mass;
+    if total_inverse_mass <= 0.0 {
+        return None;
+    }
+    let (nx, ny) = normal;
+    let (tx, ty) = (-ny, nx);
+
+    // Push them apart in proportion to how easily each moves, so the planet barely budges.
+    let correction = (depth - CORRECTION_SLOP).max(0.0) * CORRECTION_PERCENT / total_inverse_mass;
+    let mut a_resolved = Resolved {
+        push: (
+            -nx * correction * a.inverse_mass,
+            -ny * correction * a.inverse_mass,
+        ),
+        velocity: a.velocity,
+        spin: a.spin,
+    };
+    let mut b_resolved = Resolved {
+        push: (
+            nx * correction * b.inverse_mass,
+            ny * correction * b.inverse_mass,
+        ),
+        velocity: b.velocity,
+        spin: b.spin,
+    };
+
+    let relative = (b.velocity.0 - a.velocity.0, b.velocity.1 - a.velocity.1);
+    let closing_speed = relative.0 * nx + relative.1 * ny;
+    if closing_speed >= 0.0 {
+        // Already moving apart, so only the overlap needs fixing.
+        return Some((a_resolved, b_resolved, 0.0));
+    }
 
-    // TODO: Calculate some damage based on mass & velocity
+    let restitution = a.restitution.min(b.restitution);
+    let impulse = -(1.0 + restitution) * closing_speed / total_inverse_mass;
+
+    // Speed the surfaces slide past each other at where they touch, spin included. Friction
+    // works against it, but never harder than the bounce itself.
+    let sliding_speed = relative.0 * tx + relative.1 * ty - a.spin * a.radius - b.spin * b.radius;
+    // A disc's moment of inertia is half its mass times its radius squared, which makes
+    // stopping the slide take three times the impulse stopping the bounce would.
+    let friction_impulse = (-sliding_speed / (3.0 * total_inverse_mass))
+        .max(-FRICTION * impulse)
+        .min(FRICTION * impulse);
+
+    let (jx, jy) = (
+        nx * impulse + tx * friction_impulse,
+        ny * impulse + ty * friction_impulse,
+    );
+    a_resolved.velocity.0 -= jx * a.inverse_mass;
+    a_resolved.velocity.1 -= jy * a.inverse_mass;
+    b_resolved.velocity.0 += jx * b.inverse_mass;
+    b_resolved.velocity.1 += jy * b.inverse_mass;
+    if a.radius > 0.0 {
+        a_resolved.spin -= 2.0 * friction_impulse * a.inverse_mass / a.radius;
+    }
+    if b.radius > 0.0 {
+        b_resolved.spin -= 2.0 * friction_impulse * b.inverse_mass / b.radius;
+    }
 
-    Some((vf1x, vf1y, vf2x, vf2y))
+    let energy = 0.5 * closing_speed * closing_speed / total_inverse_mass;
+    Some((a_resolved, b_resolved, energy))
 }
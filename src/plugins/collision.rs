@@ -1,100 +1,1155 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::ops::{Deref, DerefMut};
 use specs::*;
+use ggez::graphics::Rect;
+use rayon::prelude::*;
 use plugins::*;
+use plugins::events::EventChannel;
 
-pub fn init<'a, 'b>(
-    world: &mut World,
-    dispatcher: DispatcherBuilder<'a, 'b>,
-) -> DispatcherBuilder<'a, 'b> {
-    world.add_resource(Collisions::new());
-    world.register::<Collidable>();
-    dispatcher.add(CollisionSystem, "collision", &[])
+// Comfortably bigger than an asteroid, so most things only land in a cell or four.
+pub const CELL_SIZE: f32 = 200.0;
+
+pub struct CollisionPlugin;
+impl Plugin for CollisionPlugin {
+    fn name(&self) -> &'static str {
+        "collision"
+    }
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec!["position_motion"]
+    }
+    fn setup(&self, world: &mut World) {
+        world.add_resource(Collisions::new());
+        world.add_resource(Contacts::new());
+        world.add_resource(ContactEvents::new());
+        world.add_resource(SpatialIndex::new(CELL_SIZE));
+        world.register::<Collidable>();
+        world.register::<FastMover>();
+    }
+    fn systems<'a, 'b>(&self, systems: &mut SystemRegistry<'a, 'b>) {
+        systems.add(Stage::Collision, SpatialIndexSystem, "spatial_index", &[]);
+        systems.add(
+            Stage::Collision,
+            CollisionSystem,
+            "collision",
+            &["spatial_index"],
+        );
+        systems.add(
+            Stage::Collision,
+            ContinuousCollisionSystem,
+            "continuous_collision",
+            &["collision"],
+        );
+    }
+}
+
+// Where two collidables touch. The normal points from a towards b, and depth is how far they
+// overlap along it.
+#[derive(Clone, Copy, Debug)]
+pub struct Contact {
+    pub a: Entity,
+    pub b: Entity,
+    pub normal: (f32, f32),
+    pub depth: f32,
+    // How far through the tick, from 0.0 to 1.0, they first touched. Always 1.0 unless a
+    // FastMover was caught part way along its path.
+    pub time_of_impact: f32,
 }
 
+// Every contact as of the last collision check, keyed by pair with the lower entity first.
+#[derive(Debug, Default)]
+pub struct Contacts(pub BTreeMap<(Entity, Entity), Contact>);
+impl Contacts {
+    pub fn new() -> Contacts {
+        Default::default()
+    }
+}
+
+// Published each tick, so gameplay can react once when something hits rather than on every tick
+// they stay touching. Ended carries the last contact seen, and also follows a despawn.
+#[derive(Clone, Copy, Debug)]
+pub enum ContactEvent {
+    Began(Contact),
+    Persisted(Contact),
+    Ended(Contact),
+}
+pub type ContactEvents = EventChannel<ContactEvent>;
+
 #[derive(Debug)]
-pub struct Collisions(pub HashMap<Entity, HashSet<Entity>>);
+pub struct Collisions(pub BTreeMap<Entity, BTreeSet<Entity>>);
 impl Collisions {
     pub fn new() -> Self {
         Default::default()
     }
     pub fn insert(&mut self, e1: Entity, e2: Entity) {
-        self.0.entry(e1).or_insert_with(HashSet::new).insert(e2);
-        self.0.entry(e2).or_insert_with(HashSet::new).insert(e1);
+        self.0.entry(e1).or_insert_with(BTreeSet::new).insert(e2);
+        self.0.entry(e2).or_insert_with(BTreeSet::new).insert(e1);
     }
     pub fn remove(&mut self, e1: Entity, e2: Entity) {
-        self.0.entry(e1).or_insert_with(HashSet::new).remove(&e2);
-        self.0.entry(e2).or_insert_with(HashSet::new).remove(&e1);
+        self.0.entry(e1).or_insert_with(BTreeSet::new).remove(&e2);
+        self.0.entry(e2).or_insert_with(BTreeSet::new).remove(&e1);
     }
 }
 impl Default for Collisions {
     fn default() -> Collisions {
-        Collisions(HashMap::new())
+        Collisions(BTreeMap::new())
     }
 }
 impl Deref for Collisions {
-    type Target = HashMap<Entity, HashSet<Entity>>;
-    fn deref(&self) -> &HashMap<Entity, HashSet<Entity>> {
+    type Target = BTreeMap<Entity, BTreeSet<Entity>>;
+    fn deref(&self) -> &BTreeMap<Entity, BTreeSet<Entity>> {
         &self.0
     }
 }
 impl DerefMut for Collisions {
-    fn deref_mut(&mut self) -> &mut HashMap<Entity, HashSet<Entity>> {
+    fn deref_mut(&mut self) -> &mut BTreeMap<Entity, BTreeSet<Entity>> {
         &mut self.0
     }
 }
 
+// Bits for Collidable layers and masks. Two collidables are only checked against each other when
+// each one's mask includes the other's layer.
+pub const LAYER_DEFAULT: u32 = 1;
+pub const LAYER_PLAYER: u32 = 1 << 1;
+pub const LAYER_PLAYER_BULLET: u32 = 1 << 2;
+pub const LAYER_PLANET: u32 = 1 << 3;
+pub const LAYER_ASTEROID: u32 = 1 << 4;
+pub const LAYER_POWER_UP: u32 = 1 << 5;
+pub const LAYER_ALL: u32 = !0;
+
+pub fn layers_interact(a_layer: u32, a_mask: u32, b_layer: u32, b_mask: u32) -> bool {
+    a_mask & b_layer != 0 && b_mask & a_layer != 0
+}
+
 #[derive(Component, Debug)]
 pub struct Collidable {
+    pub shape: CollisionShape,
+    // Which layer this is on, and which layers it collides with.
+    pub layer: u32,
+    pub mask: u32,
+}
+impl Collidable {
+    pub fn new(shape: CollisionShape) -> Collidable {
+        Collidable {
+            shape,
+            layer: LAYER_DEFAULT,
+            mask: LAYER_ALL,
+        }
+    }
+    // A circle of the given diameter.
+    pub fn circle(size: f32) -> Collidable {
+        Collidable::new(CollisionShape::Circle { radius: size / 2.0 })
+    }
+    // Follows the sprite's outline where it has a fixed one, or else a circle as wide as it is.
+    pub fn from_sprite(sprite: &sprites::Sprite) -> Collidable {
+        match CollisionShape::from_sprite(sprite) {
+            Some(shape) => Collidable::new(shape),
+            None => Collidable::circle(sprite.scale.x),
+        }
+    }
+    pub fn with_layer(mut self, layer: u32, mask: u32) -> Collidable {
+        self.layer = layer;
+        self.mask = mask;
+        self
+    }
+    // Diameter of a circle around the whole shape, for the broadphase and spawn checks.
+    pub fn size(&self) -> f32 {
+        self.shape.bounding_size()
+    }
+}
+
+// Points are relative to the entity's position. Everything but Circle and Aabb turns with the
+// entity's rotation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CollisionShape {
+    Circle { radius: f32 },
+    Aabb { half_width: f32, half_height: f32 },
+    OrientedBox { half_width: f32, half_height: f32 },
+    // A line from (0, -half_length) to (0, half_length), thickened by radius.
+    Capsule { half_length: f32, radius: f32 },
+    // Must be convex - use convex_hull if it might not be.
+    Polygon { points: Vec<(f32, f32)> },
+}
+impl CollisionShape {
+    pub fn from_sprite(sprite: &sprites::Sprite) -> Option<CollisionShape> {
+        sprite.shape.outline().map(|outline| {
+            let points: Vec<(f32, f32)> = outline
+                .iter()
+                .map(|&(x, y)| {
+                    (
+                        (x - sprite.offset.x) * sprite.scale.x,
+                        (y - sprite.offset.y) * sprite.scale.y,
+                    )
+                })
+                .collect();
+            CollisionShape::convex_hull(&points)
+        })
+    }
+
+    // The smallest convex polygon around all the points. Points that aren't finite, e.g. from a
+    // shape scaled by a bad config value, are left out.
+    pub fn convex_hull(points: &[(f32, f32)]) -> CollisionShape {
+        let mut sorted: Vec<(f32, f32)> = points
+            .iter()
+            .cloned()
+            .filter(|&(x, y)| x.is_finite() && y.is_finite())
+            .collect();
+        sorted.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+        sorted.dedup();
+        if sorted.len() < 3 {
+            return CollisionShape::Polygon { points: sorted };
+        }
+        // Andrew's monotone chain: build the lower then upper halves, dropping inward turns.
+        let mut hull: Vec<(f32, f32)> = Vec::new();
+        for pass in 0..2 {
+            let start = hull.len();
+            for &point in &sorted {
+                while hull.len() >= start + 2
+                    && cross(hull[hull.len() - 2], hull[hull.len() - 1], point) <= 0.0
+                {
+                    hull.pop();
+                }
+                hull.push(point);
+            }
+            // The last point of each half is the first of the other.
+            hull.pop();
+            if pass == 0 {
+                sorted.reverse();
+            }
+        }
+        CollisionShape::Polygon { points: hull }
+    }
+
+    pub fn bounding_size(&self) -> f32 {
+        2.0 * match *self {
+            CollisionShape::Circle { radius } => radius,
+            CollisionShape::Aabb {
+                half_width,
+                half_height,
+            }
+            | CollisionShape::OrientedBox {
+                half_width,
+                half_height,
+            } => half_width.hypot(half_height),
+            CollisionShape::Capsule {
+                half_length,
+                radius,
+            } => half_length + radius,
+            CollisionShape::Polygon { ref points } => {
+                points.iter().map(|&(x, y)| x.hypot(y)).fold(0.0, f32::max)
+            }
+        }
+    }
+
+    // Every shape is treated as a convex core - a point, a line, or a polygon - with a radius
+    // around it, in world space.
+    fn core(&self, pos: &position_motion::Position) -> (Vec<(f32, f32)>, f32) {
+        let (sin, cos) = pos.r.sin_cos();
+        let place = |&(x, y): &(f32, f32)| (pos.x + x * cos - y * sin, pos.y + x * sin + y * cos);
+        match *self {
+            CollisionShape::Circle { radius } => (vec![(pos.x, pos.y)], radius),
+            CollisionShape::Aabb {
+                half_width: w,
+                half_height: h,
+            } => (
+                vec![
+                    (pos.x - w, pos.y - h),
+                    (pos.x + w, pos.y - h),
+                    (pos.x + w, pos.y + h),
+                    (pos.x - w, pos.y + h),
+                ],
+                0.0,
+            ),
+            CollisionShape::OrientedBox {
+                half_width: w,
+                half_height: h,
+            } => (
+                [(-w, -h), (w, -h), (w, h), (-w, h)]
+                    .iter()
+                    .map(place)
+                    .collect(),
+                0.0,
+            ),
+            CollisionShape::Capsule {
+                half_length,
+                radius,
+            } => (
+                [(0.0, -half_length), (0.0, half_length)]
+                    .iter()
+                    .map(place)
+                    .collect(),
+                radius,
+            ),
+            CollisionShape::Polygon { ref points } => (points.iter().map(place).collect(), 0.0),
+        }
+    }
+}
+
+// Narrowphase test, by the separating axis theorem: two convex shapes overlap unless there's a
+// line they can be projected onto without their projections overlapping. When they do overlap,
+// returns the normal pointing from a towards b, and how far b would have to move along it to
+// stop overlapping.
+pub fn contact(
+    a: &CollisionShape,
+    a_pos: &position_motion::Position,
+    b: &CollisionShape,
+    b_pos: &position_motion::Position,
+) -> Option<((f32, f32), f32)> {
+    let between = (b_pos.x - a_pos.x, b_pos.y - a_pos.y);
+    if let (
+        &CollisionShape::Circle { radius: a_radius },
+        &CollisionShape::Circle { radius: b_radius },
+    ) = (a, b)
+    {
+        let distance = between.0.hypot(between.1);
+        let depth = a_radius + b_radius - distance;
+        if depth < 0.0 {
+            return None;
+        }
+        // Right on top of each other, so any direction will do.
+        if distance == 0.0 {
+            return Some(((0.0, 1.0), depth));
+        }
+        return Some(((between.0 / distance, between.1 / distance), depth));
+    }
+    let (a_core, a_radius) = a.core(a_pos);
+    let (b_core, b_radius) = b.core(b_pos);
+    if a_core.is_empty() || b_core.is_empty() {
+        return None;
+    }
+
+    // Only the edge normals need checking for plain polygons. With a radius, the shapes can also
+    // be separated along the line from a corner to the nearest point of the other shape.
+    let mut axes = Vec::new();
+    edge_normals(&a_core, &mut axes);
+    edge_normals(&b_core, &mut axes);
+    if a_radius > 0.0 || b_radius > 0.0 {
+        for &point in &a_core {
+            axes.push(sub(closest_point(&b_core, point), point));
+        }
+        for &point in &b_core {
+            axes.push(sub(closest_point(&a_core, point), point));
+        }
+    }
+
+    // The axis they overlap least along is the quickest way to push them apart.
+    let mut normal = (0.0, 1.0);
+    let mut depth = f32::INFINITY;
+    for axis in axes {
+        let length = axis.0.hypot(axis.1);
+        if length == 0.0 {
+            continue;
+        }
+        let axis = (axis.0 / length, axis.1 / length);
+        let (a_min, a_max) = project(&a_core, axis);
+        let (b_min, b_max) = project(&b_core, axis);
+        let overlap =
+            (a_max + a_radius).min(b_max + b_radius) - (a_min - a_radius).max(b_min - b_radius);
+        if overlap < 0.0 {
+            return None;
+        }
+        if overlap < depth {
+            depth = overlap;
+            normal = if dot(axis, between) < 0.0 {
+                (-axis.0, -axis.1)
+            } else {
+                axis
+            };
+        }
+    }
+    if depth == f32::INFINITY {
+        // Two points in the same place.
+        depth = a_radius + b_radius;
+    }
+    Some((normal, depth))
+}
+
+pub fn shapes_overlap(
+    a: &CollisionShape,
+    a_pos: &position_motion::Position,
+    b: &CollisionShape,
+    b_pos: &position_motion::Position,
+) -> bool {
+    contact(a, a_pos, b, b_pos).is_some()
+}
+
+fn sub(a: (f32, f32), b: (f32, f32)) -> (f32, f32) {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn dot(a: (f32, f32), b: (f32, f32)) -> f32 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+// Positive when o -> a -> b turns counter-clockwise.
+fn cross(o: (f32, f32), a: (f32, f32), b: (f32, f32)) -> f32 {
+    (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+}
+
+// A line has one edge, a polygon wraps around to close.
+fn edges(core: &[(f32, f32)]) -> Vec<((f32, f32), (f32, f32))> {
+    match core.len() {
+        0 | 1 => Vec::new(),
+        2 => vec![(core[0], core[1])],
+        len => (0..len)
+            .map(|idx| (core[idx], core[(idx + 1) % len]))
+            .collect(),
+    }
+}
+
+fn edge_normals(core: &[(f32, f32)], axes: &mut Vec<(f32, f32)>) {
+    for (a, b) in edges(core) {
+        axes.push((a.1 - b.1, b.0 - a.0));
+    }
+}
+
+fn closest_point(core: &[(f32, f32)], point: (f32, f32)) -> (f32, f32) {
+    if core.len() == 1 {
+        return core[0];
+    }
+    let mut closest = core[0];
+    let mut closest_distance = f32::INFINITY;
+    for (a, b) in edges(core) {
+        let edge = sub(b, a);
+        let length_sq = dot(edge, edge);
+        let t = if length_sq > 0.0 {
+            (dot(sub(point, a), edge) / length_sq).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let candidate = (a.0 + edge.0 * t, a.1 + edge.1 * t);
+        let offset = sub(candidate, point);
+        let distance = dot(offset, offset);
+        if distance < closest_distance {
+            closest = candidate;
+            closest_distance = distance;
+        }
+    }
+    closest
+}
+
+fn project(core: &[(f32, f32)], axis: (f32, f32)) -> (f32, f32) {
+    core.iter()
+        .map(|&point| dot(point, axis))
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), d| {
+            (min.min(d), max.max(d))
+        })
+}
+
+// Circles of the given diameters - the broadphase's rough test.
+pub fn overlaps(ax: f32, ay: f32, a_size: f32, bx: f32, by: f32, b_size: f32) -> bool {
+    let overlap_range = ((a_size / 2.0) + (b_size / 2.0)).powf(2.0);
+    let distance_sq = (bx - ax).powf(2.0) + (by - ay).powf(2.0);
+    distance_sq <= overlap_range
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct IndexEntry {
+    pub entity: Entity,
+    pub x: f32,
+    pub y: f32,
     pub size: f32,
+    pub layer: u32,
+    pub mask: u32,
+}
+
+// Uniform grid of every collidable, rebuilt each tick before collision detection. Each entry goes
+// in every cell its bounding box touches, so a query only has to look at the cells it covers.
+pub struct SpatialIndex {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<IndexEntry>>,
+}
+impl SpatialIndex {
+    pub fn new(cell_size: f32) -> SpatialIndex {
+        SpatialIndex {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    pub fn insert(&mut self, entity: Entity, x: f32, y: f32, collidable: &Collidable) {
+        let size = collidable.size();
+        // Something flung off to infinity would otherwise cover every cell there is.
+        if !x.is_finite() || !y.is_finite() || !size.is_finite() {
+            return;
+        }
+        let entry = IndexEntry {
+            entity,
+            x,
+            y,
+            size,
+            layer: collidable.layer,
+            mask: collidable.mask,
+        };
+        let half = size / 2.0;
+        let (min_col, min_row) = self.cell(x - half, y - half);
+        let (max_col, max_row) = self.cell(x + half, y + half);
+        for col in min_col..=max_col {
+            for row in min_row..=max_row {
+                self.cells.entry((col, row)).or_default().push(entry);
+            }
+        }
+    }
+
+    // Everything whose bounding box overlaps the rect, in entity order.
+    pub fn query_region(&self, rect: &Rect) -> Vec<IndexEntry> {
+        let mut found = Vec::new();
+        self.visit_cells(rect.x, rect.y, rect.x + rect.w, rect.y + rect.h, |entry| {
+            let half = entry.size / 2.0;
+            if entry.x + half >= rect.x
+                && entry.x - half <= rect.x + rect.w
+                && entry.y + half >= rect.y
+                && entry.y - half <= rect.y + rect.h
+            {
+                found.push(*entry);
+            }
+        });
+        dedup(found)
+    }
+
+    // Everything overlapping a circle of the given diameter, in entity order.
+    pub fn query_radius(&self, x: f32, y: f32, size: f32) -> Vec<IndexEntry> {
+        let half = size / 2.0;
+        let mut found = Vec::new();
+        self.visit_cells(x - half, y - half, x + half, y + half, |entry| {
+            if overlaps(x, y, size, entry.x, entry.y, entry.size) {
+                found.push(*entry);
+            }
+        });
+        dedup(found)
+    }
+
+    // Every overlapping pair whose layers interact, each once with the lower entity first, in
+    // entity order.
+    pub fn overlapping_pairs(&self) -> Vec<(Entity, Entity)> {
+        // Each cell is checked on its own, so they're spread across threads.
+        let mut pairs: Vec<(Entity, Entity)> = self
+            .cells
+            .par_iter()
+            .flat_map(|(_, entries)| {
+                let mut pairs = Vec::new();
+                for (idx, a) in entries.iter().enumerate() {
+                    for b in &entries[idx + 1..] {
+                        if a.entity != b.entity
+                            && layers_interact(a.layer, a.mask, b.layer, b.mask)
+                            && overlaps(a.x, a.y, a.size, b.x, b.y, b.size)
+                        {
+                            pairs.push(pair(a.entity, b.entity));
+                        }
+                    }
+                }
+                pairs
+            })
+            .collect();
+        // Pairs sharing more than one cell turn up once per cell, and sorting also undoes
+        // whatever order the threads finished in.
+        pairs.sort();
+        pairs.dedup();
+        pairs
+    }
+
+    fn cell(&self, x: f32, y: f32) -> (i32, i32) {
+        (
+            (x / self.cell_size).floor() as i32,
+            (y / self.cell_size).floor() as i32,
+        )
+    }
+
+    fn visit_cells<F: FnMut(&IndexEntry)>(&self, x1: f32, y1: f32, x2: f32, y2: f32, mut f: F) {
+        let (min_col, min_row) = self.cell(x1, y1);
+        let (max_col, max_row) = self.cell(x2, y2);
+        for col in min_col..=max_col {
+            for row in min_row..=max_row {
+                if let Some(entries) = self.cells.get(&(col, row)) {
+                    for entry in entries {
+                        f(entry);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn dedup(mut entries: Vec<IndexEntry>) -> Vec<IndexEntry> {
+    entries.sort_by_key(|entry| entry.entity);
+    entries.dedup_by_key(|entry| entry.entity);
+    entries
+}
+
+pub struct SpatialIndexSystem;
+impl<'a> System<'a> for SpatialIndexSystem {
+    type SystemData = (
+        Entities<'a>,
+        FetchMut<'a, SpatialIndex>,
+        ReadStorage<'a, position_motion::Position>,
+        ReadStorage<'a, Collidable>,
+    );
+
+    fn run(&mut self, (entities, mut index, positions, collidables): Self::SystemData) {
+        index.clear();
+        for (entity, pos, col) in (&*entities, &positions, &collidables).join() {
+            index.insert(entity, pos.x, pos.y, col);
+        }
+    }
 }
 
 pub struct CollisionSystem;
 
 impl<'a> System<'a> for CollisionSystem {
     type SystemData = (
-        Entities<'a>,
         FetchMut<'a, Collisions>,
+        FetchMut<'a, Contacts>,
+        FetchMut<'a, ContactEvents>,
+        Fetch<'a, SpatialIndex>,
         ReadStorage<'a, position_motion::Position>,
         ReadStorage<'a, Collidable>,
     );
 
-    fn run(&mut self, (entities, mut collisions, positions, collidables): Self::SystemData) {
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut collisions, mut contacts, mut contact_events, index, positions, collidables) =
+            data;
         collisions.clear();
-        // TODO: Replace this compare of all-to-all with a quadtree search
-        for (entity, pos, col) in (&*entities, &positions, &collidables).join() {
-            for (other_entity, other_pos, other_col) in
-                (&*entities, &positions, &collidables).join()
-            {
-                if entity == other_entity {
+        let found: Vec<Contact> = index
+            .overlapping_pairs()
+            .par_iter()
+            .filter_map(|&(a, b)| {
+                let (a_pos, a_col) = (positions.get(a)?, collidables.get(a)?);
+                let (b_pos, b_col) = (positions.get(b)?, collidables.get(b)?);
+                let (normal, depth) = contact(&a_col.shape, a_pos, &b_col.shape, b_pos)?;
+                Some(Contact {
+                    a,
+                    b,
+                    normal,
+                    depth,
+                    time_of_impact: 1.0,
+                })
+            })
+            .collect();
+        let mut current = BTreeMap::new();
+        for contact in found {
+            collisions.insert(contact.a, contact.b);
+            current.insert((contact.a, contact.b), contact);
+        }
+
+        let mut events = Vec::new();
+        for (pair, contact) in &current {
+            events.push(if contacts.0.contains_key(pair) {
+                ContactEvent::Persisted(*contact)
+            } else {
+                ContactEvent::Began(*contact)
+            });
+        }
+        for (pair, contact) in &contacts.0 {
+            if !current.contains_key(pair) {
+                events.push(ContactEvent::Ended(*contact));
+            }
+        }
+        contact_events.iter_write(events);
+        contacts.0 = current;
+    }
+}
+
+// Marks something that moves far enough in a tick to pass right through things, like a bullet.
+// Its whole path since the last tick is checked, not just where it ended up.
+#[derive(Component, Debug)]
+pub struct FastMover;
+
+// Catches fast movers that passed through something between ticks, which the check of where
+// things ended up misses. Each one stops at the first thing in its path, and is moved back to
+// where it hit.
+pub struct ContinuousCollisionSystem;
+impl<'a> System<'a> for ContinuousCollisionSystem {
+    type SystemData = (
+        Entities<'a>,
+        FetchMut<'a, Collisions>,
+        FetchMut<'a, Contacts>,
+        FetchMut<'a, ContactEvents>,
+        Fetch<'a, SpatialIndex>,
+        ReadStorage<'a, FastMover>,
+        ReadStorage<'a, position_motion::PreviousPosition>,
+        WriteStorage<'a, position_motion::Position>,
+        ReadStorage<'a, Collidable>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            mut collisions,
+            mut contacts,
+            mut contact_events,
+            index,
+            fast_movers,
+            previous_positions,
+            mut positions,
+            collidables,
+        ) = data;
+
+        let mut hits = Vec::new();
+        for (entity, _fast_mover, previous, col) in
+            (&*entities, &fast_movers, &previous_positions, &collidables).join()
+        {
+            let pos = match positions.get(entity) {
+                Some(pos) => pos,
+                None => continue,
+            };
+            let from = position_motion::Position {
+                x: previous.x,
+                y: previous.y,
+                r: pos.r,
+            };
+            if from.x == pos.x && from.y == pos.y {
+                continue;
+            }
+            let half = col.size() / 2.0;
+            let path = Rect::new(
+                from.x.min(pos.x) - half,
+                from.y.min(pos.y) - half,
+                (pos.x - from.x).abs() + half * 2.0,
+                (pos.y - from.y).abs() + half * 2.0,
+            );
+            let mut first: Option<(f32, Entity)> = None;
+            for other in index.query_region(&path) {
+                if other.entity == entity
+                    || !layers_interact(col.layer, col.mask, other.layer, other.mask)
+                    || contacts.0.contains_key(&pair(entity, other.entity))
+                {
                     continue;
                 }
-                // Simple circular overlap "hitbox" - TODO: implement more complex logic
-                let overlap_range = ((col.size / 2.0) + (other_col.size / 2.0)).powf(2.0);
-                let distance_sq = (other_pos.x - pos.x).powf(2.0) + (other_pos.y - pos.y).powf(2.0);
-                if distance_sq <= overlap_range {
-                    collisions.insert(entity, other_entity);
+                let (other_pos, other_col) =
+                    match (positions.get(other.entity), collidables.get(other.entity)) {
+                        (Some(other_pos), Some(other_col)) => (other_pos, other_col),
+                        _ => continue,
+                    };
+                if let Some(time) =
+                    time_of_impact(&col.shape, &from, pos, &other_col.shape, other_pos)
+                {
+                    match first {
+                        Some((first_time, _)) if first_time <= time => {}
+                        _ => first = Some((time, other.entity)),
+                    }
                 }
             }
+            if let Some((time, other)) = first {
+                let at = position_motion::Position {
+                    x: from.x + (pos.x - from.x) * time,
+                    y: from.y + (pos.y - from.y) * time,
+                    r: pos.r,
+                };
+                let (normal, depth) = {
+                    let other_pos = positions.get(other).unwrap();
+                    let other_col = collidables.get(other).unwrap();
+                    contact(&col.shape, &at, &other_col.shape, other_pos)
+                        .unwrap_or(((0.0, 1.0), 0.0))
+                };
+                hits.push((entity, other, at, normal, depth, time));
+            }
         }
+
+        let mut events = Vec::new();
+        for (entity, other, at, normal, depth, time) in hits {
+            if let Some(pos) = positions.get_mut(entity) {
+                *pos = at;
+            }
+            let (a, b) = pair(entity, other);
+            // The normal was worked out from the fast mover's side.
+            let normal = if a == entity {
+                normal
+            } else {
+                (-normal.0, -normal.1)
+            };
+            let contact = Contact {
+                a,
+                b,
+                normal,
+                depth,
+                time_of_impact: time,
+            };
+            collisions.insert(a, b);
+            contacts.0.insert((a, b), contact);
+            events.push(ContactEvent::Began(contact));
+        }
+        contact_events.iter_write(events);
     }
 }
 
-pub fn is_empty_at(
-    positions: &ReadStorage<position_motion::Position>,
-    collidables: &ReadStorage<Collidable>,
-    x: f32,
-    y: f32,
-    size: f32,
-) -> bool {
-    // TODO: optimize this with quadtree index
-    for (other_pos, other_col) in (&*positions, &*collidables).join() {
-        // TODO: DRY this out with collision detection in CollisionSystem
-        let overlap_range = ((size / 2.0) + (other_col.size / 2.0)).powf(2.0);
-        let distance_sq = (other_pos.x - x).powf(2.0) + (other_pos.y - y).powf(2.0);
-        if distance_sq <= overlap_range {
-            return false;
+fn pair(a: Entity, b: Entity) -> (Entity, Entity) {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+// When, from 0.0 to 1.0, a shape moving from one position to another first touches another
+// shape, or None if it never does. The other shape is taken to be standing still where it is.
+pub fn time_of_impact(
+    shape: &CollisionShape,
+    from: &position_motion::Position,
+    to: &position_motion::Position,
+    other: &CollisionShape,
+    other_pos: &position_motion::Position,
+) -> Option<f32> {
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    let (enter, exit) = sweep_circle(
+        (from.x, from.y),
+        (dx, dy),
+        (shape.bounding_size() + other.bounding_size()) / 2.0,
+        (other_pos.x, other_pos.y),
+    )?;
+    let at = |time: f32| position_motion::Position {
+        x: from.x + dx * time,
+        y: from.y + dy * time,
+        r: to.r,
+    };
+    if let (&CollisionShape::Circle { .. }, &CollisionShape::Circle { .. }) = (shape, other) {
+        return Some(enter);
+    }
+
+    // Anything else only fills part of its bounding circle, so step through the stretch where the
+    // circles overlap, no further apart than a quarter of the smaller shape, and then narrow down
+    // where they first touch.
+    let spacing = shape.bounding_size().min(other.bounding_size()) / 4.0;
+    let distance = (exit - enter) * dx.hypot(dy);
+    let steps = if spacing > 0.0 {
+        ((distance / spacing).ceil() as usize).clamp(1, 64)
+    } else {
+        1
+    };
+    let mut missed = enter;
+    for step in 0..=steps {
+        let time = enter + (exit - enter) * step as f32 / steps as f32;
+        if shapes_overlap(shape, &at(time), other, other_pos) {
+            if step == 0 {
+                return Some(time);
+            }
+            let mut hit = time;
+            for _ in 0..8 {
+                let middle = (missed + hit) / 2.0;
+                if shapes_overlap(shape, &at(middle), other, other_pos) {
+                    hit = middle;
+                } else {
+                    missed = middle;
+                }
+            }
+            return Some(hit);
         }
+        missed = time;
+    }
+    None
+}
+
+// When a circle moving from start by delta comes within radius of a point, as the times from 0.0
+// to 1.0 that it enters and leaves, or None if it doesn't.
+fn sweep_circle(
+    start: (f32, f32),
+    delta: (f32, f32),
+    radius: f32,
+    point: (f32, f32),
+) -> Option<(f32, f32)> {
+    let offset = sub(start, point);
+    let a = dot(delta, delta);
+    let c = dot(offset, offset) - radius * radius;
+    if a == 0.0 {
+        return if c <= 0.0 { Some((0.0, 0.0)) } else { None };
+    }
+    let b = 2.0 * dot(offset, delta);
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    let enter = (-b - root) / (2.0 * a);
+    let exit = (-b + root) / (2.0 * a);
+    if exit < 0.0 || enter > 1.0 {
+        return None;
+    }
+    Some((enter.max(0.0), exit.min(1.0)))
+}
+
+// Whether something of the given size could be placed here without overlapping anything that was
+// collidable as of the last collision check.
+pub fn is_empty_at(index: &SpatialIndex, x: f32, y: f32, size: f32) -> bool {
+    index.query_radius(x, y, size).is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugins::position_motion::{Position, PreviousPosition};
+
+    fn at(x: f32, y: f32) -> Position {
+        Position { x, y, r: 0.0 }
+    }
+
+    fn square(half: f32) -> CollisionShape {
+        CollisionShape::Polygon {
+            points: vec![(-half, -half), (half, -half), (half, half), (-half, half)],
+        }
+    }
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.register::<Position>();
+        world.register::<PreviousPosition>();
+        CollisionPlugin.setup(&mut world);
+        world
+            .write_resource::<ContactEvents>()
+            .register_reader("test");
+        world
+    }
+
+    fn spawn(world: &mut World, x: f32, y: f32, shape: CollisionShape) -> Entity {
+        world
+            .create_entity()
+            .with(at(x, y))
+            .with(Collidable::new(shape))
+            .build()
+    }
+
+    fn tick(world: &mut World) -> Vec<ContactEvent> {
+        SpatialIndexSystem.run_now(&world.res);
+        CollisionSystem.run_now(&world.res);
+        ContinuousCollisionSystem.run_now(&world.res);
+        world
+            .read_resource::<ContactEvents>()
+            .read("test")
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn circles() {
+        let circle = CollisionShape::Circle { radius: 10.0 };
+        let (normal, depth) = contact(&circle, &at(0.0, 0.0), &circle, &at(15.0, 0.0)).unwrap();
+        assert_eq!(normal, (1.0, 0.0));
+        assert_eq!(depth, 5.0);
+        assert!(contact(&circle, &at(0.0, 0.0), &circle, &at(25.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn circle_and_polygon() {
+        let circle = CollisionShape::Circle { radius: 10.0 };
+        let (normal, depth) =
+            contact(&square(10.0), &at(0.0, 0.0), &circle, &at(0.0, 18.0)).unwrap();
+        assert!((normal.0).abs() < 1e-5 && (normal.1 - 1.0).abs() < 1e-5);
+        assert!((depth - 2.0).abs() < 1e-4);
+        // Just past its corner.
+        assert!(contact(&square(10.0), &at(0.0, 0.0), &circle, &at(18.0, 18.0)).is_none());
+    }
+
+    #[test]
+    fn rotated_polygons() {
+        let diamond = Position {
+            x: 23.0,
+            y: 0.0,
+            r: ::std::f32::consts::FRAC_PI_4,
+        };
+        // Unrotated the two squares would be 3 apart, but turned the second reaches its corner
+        // over.
+        assert!(contact(&square(10.0), &at(0.0, 0.0), &square(10.0), &at(23.0, 0.0)).is_none());
+        let (normal, _) = contact(&square(10.0), &at(0.0, 0.0), &square(10.0), &diamond).unwrap();
+        assert!(normal.0 > 0.0);
+    }
+
+    #[test]
+    fn convex_hull_drops_inner_and_bad_points() {
+        let points = [
+            (0.0, 0.0),
+            (10.0, 0.0),
+            (5.0, 2.0),
+            (f32::NAN, 3.0),
+            (10.0, 10.0),
+            (0.0, 10.0),
+            (0.0, 0.0),
+            (4.0, f32::INFINITY),
+        ];
+        match CollisionShape::convex_hull(&points) {
+            CollisionShape::Polygon { points } => {
+                assert_eq!(
+                    points,
+                    vec![(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)]
+                )
+            }
+            shape => panic!("expected a polygon, got {:?}", shape),
+        }
+        match CollisionShape::convex_hull(&[(f32::NAN, f32::NAN)]) {
+            CollisionShape::Polygon { points } => assert!(points.is_empty()),
+            shape => panic!("expected a polygon, got {:?}", shape),
+        }
+    }
+
+    #[test]
+    fn sweeps() {
+        assert_eq!(
+            sweep_circle((-20.0, 0.0), (40.0, 0.0), 10.0, (0.0, 0.0)),
+            Some((0.25, 0.75))
+        );
+        assert_eq!(
+            sweep_circle((-20.0, 20.0), (40.0, 0.0), 10.0, (0.0, 0.0)),
+            None
+        );
+        // Stops short of it.
+        assert_eq!(
+            sweep_circle((-40.0, 0.0), (20.0, 0.0), 10.0, (0.0, 0.0)),
+            None
+        );
+
+        let bullet = CollisionShape::Circle { radius: 1.0 };
+        let time = time_of_impact(
+            &bullet,
+            &at(-100.0, 0.0),
+            &at(100.0, 0.0),
+            &square(10.0),
+            &at(0.0, 0.0),
+        )
+        .unwrap();
+        // First touches at x = -11.
+        assert!((time - 0.445).abs() < 0.01);
+        assert!(time_of_impact(
+            &bullet,
+            &at(-100.0, 12.0),
+            &at(100.0, 12.0),
+            &square(10.0),
+            &at(0.0, 0.0)
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn contacts_begin_persist_and_end() {
+        let mut world = world();
+        let circle = CollisionShape::Circle { radius: 10.0 };
+        let a = spawn(&mut world, 0.0, 0.0, circle.clone());
+        let b = spawn(&mut world, 15.0, 0.0, circle);
+
+        match tick(&mut world).as_slice() {
+            [ContactEvent::Began(contact)] => assert_eq!((contact.a, contact.b), (a, b)),
+            events => panic!("expected contact to begin, got {:?}", events),
+        }
+        assert!(world.read_resource::<Collisions>()[&a].contains(&b));
+        match tick(&mut world).as_slice() {
+            [ContactEvent::Persisted(_)] => {}
+            events => panic!("expected contact to persist, got {:?}", events),
+        }
+
+        world.write::<Position>().get_mut(b).unwrap().x = 50.0;
+        match tick(&mut world).as_slice() {
+            [ContactEvent::Ended(contact)] => assert_eq!((contact.a, contact.b), (a, b)),
+            events => panic!("expected contact to end, got {:?}", events),
+        }
+        assert!(world.read_resource::<Contacts>().0.is_empty());
+        assert!(tick(&mut world).is_empty());
+    }
+
+    #[test]
+    fn fast_movers_do_not_tunnel() {
+        let mut world = world();
+        let wall = spawn(&mut world, 0.0, 0.0, square(10.0));
+        let bullet = world
+            .create_entity()
+            .with(at(500.0, 0.0))
+            .with(PreviousPosition {
+                x: -500.0,
+                y: 0.0,
+                r: 0.0,
+            })
+            .with(Collidable::new(CollisionShape::Circle { radius: 1.0 }))
+            .with(FastMover)
+            .build();
+
+        match tick(&mut world).as_slice() {
+            [ContactEvent::Began(contact)] => {
+                assert_eq!((contact.a, contact.b), (wall, bullet));
+                assert!(contact.time_of_impact < 0.5);
+            }
+            events => panic!("expected the bullet to hit the wall, got {:?}", events),
+        }
+        // Moved back to where it hit.
+        let x = world.read::<Position>().get(bullet).unwrap().x;
+        assert!((x + 11.0).abs() < 0.5, "bullet ended up at {}", x);
+    }
+
+    fn entities(count: usize) -> Vec<Entity> {
+        let world = World::new();
+        (0..count).map(|_| world.entities().create()).collect()
+    }
+
+    fn found(entries: Vec<IndexEntry>) -> Vec<Entity> {
+        entries.iter().map(|entry| entry.entity).collect()
+    }
+
+    #[test]
+    fn index_spans_cells() {
+        let e = entities(3);
+        let mut index = SpatialIndex::new(CELL_SIZE);
+        // Big enough to spread over several cells around the origin.
+        index.insert(e[0], 0.0, 0.0, &Collidable::circle(CELL_SIZE * 2.5));
+        index.insert(
+            e[1],
+            CELL_SIZE * 1.5,
+            CELL_SIZE * 1.5,
+            &Collidable::circle(10.0),
+        );
+        index.insert(e[2], CELL_SIZE * 5.5, 0.0, &Collidable::circle(10.0));
+
+        // Each of the big one's cells finds it, once.
+        for &(x, y) in &[(-1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+            let rect = Rect::new(x * CELL_SIZE, y * CELL_SIZE, 1.0, 1.0);
+            assert_eq!(found(index.query_region(&rect)), vec![e[0]]);
+        }
+        let everything = Rect::new(
+            -CELL_SIZE * 2.0,
+            -CELL_SIZE * 2.0,
+            CELL_SIZE * 4.0,
+            CELL_SIZE * 4.0,
+        );
+        assert_eq!(found(index.query_region(&everything)), vec![e[0], e[1]]);
+        assert_eq!(
+            found(index.query_radius(CELL_SIZE * 5.5, 0.0, 20.0)),
+            vec![e[2]]
+        );
+        assert!(is_empty_at(&index, CELL_SIZE * 3.5, 0.0, 20.0));
+    }
+
+    #[test]
+    fn overlapping_pairs_once_each() {
+        let e = entities(4);
+        let mut index = SpatialIndex::new(CELL_SIZE);
+        // These two straddle the same four cells.
+        index.insert(e[1], 0.0, 0.0, &Collidable::circle(100.0));
+        index.insert(e[0], 40.0, 0.0, &Collidable::circle(100.0));
+        // Overlapping, but on layers that ignore each other.
+        index.insert(
+            e[2],
+            0.0,
+            20.0,
+            &Collidable::circle(100.0).with_layer(LAYER_PLANET, LAYER_ASTEROID),
+        );
+        // Far away from everything.
+        index.insert(e[3], 1000.0, 1000.0, &Collidable::circle(100.0));
+
+        assert_eq!(index.overlapping_pairs(), vec![(e[0], e[1])]);
+    }
+
+    #[test]
+    fn index_forgets_removed_entities() {
+        let e = entities(2);
+        let mut index = SpatialIndex::new(CELL_SIZE);
+        index.insert(e[0], 0.0, 0.0, &Collidable::circle(100.0));
+        index.insert(e[1], 10.0, 0.0, &Collidable::circle(100.0));
+        assert_eq!(index.overlapping_pairs(), vec![(e[0], e[1])]);
+
+        // Rebuilt without the second, the way SpatialIndexSystem does after a despawn.
+        index.clear();
+        index.insert(e[0], 0.0, 0.0, &Collidable::circle(100.0));
+        assert!(index.overlapping_pairs().is_empty());
+        assert_eq!(found(index.query_radius(10.0, 0.0, 100.0)), vec![e[0]]);
+
+        // Nowhere sensible to put it, so it isn't indexed at all.
+        index.insert(e[1], f32::NAN, 0.0, &Collidable::circle(100.0));
+        assert_eq!(found(index.query_radius(10.0, 0.0, 100.0)), vec![e[0]]);
     }
-    true
 }
@@ -1,28 +1,48 @@
 use ggez::*;
 use ggez::graphics::*;
 use specs::*;
+use rayon::prelude::*;
 use plugins::*;
-use std::ops::Deref;
+use plugins::events::EventChannel;
 use DeltaTime;
 
-pub fn init<'a, 'b>(
-    world: &mut World,
-    dispatcher: DispatcherBuilder<'a, 'b>,
-) -> DispatcherBuilder<'a, 'b> {
-    world.add_resource(DespawnEventQueue::new());
-    world.register::<Timeout>();
-    world.register::<DespawnBounds>();
-    world.register::<DespawnOnCollision>();
-    dispatcher
-        .add(TimeoutSystem, "timeout_system", &[])
-        .add(DespawnBoundsSystem, "despawn_bounds_system", &[])
-        .add(DespawnOnCollisionSystem, "despawn_on_collision_system", &[])
+pub struct DespawnPlugin;
+impl Plugin for DespawnPlugin {
+    fn name(&self) -> &'static str {
+        "despawn"
+    }
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec!["position_motion", "collision"]
+    }
+    fn setup(&self, world: &mut World) {
+        let mut despawn_events = DespawnEventQueue::new();
+        despawn_events.register_reader("despawn");
+        world.add_resource(despawn_events);
+        world.register::<Timeout>();
+        world.register::<DespawnBounds>();
+        world.register::<DespawnOnCollision>();
+    }
+    fn systems<'a, 'b>(&self, systems: &mut SystemRegistry<'a, 'b>) {
+        systems.add(Stage::Despawn, TimeoutSystem, "timeout_system", &[]);
+        systems.add(
+            Stage::Despawn,
+            DespawnBoundsSystem,
+            "despawn_bounds_system",
+            &[],
+        );
+        systems.add(
+            Stage::Despawn,
+            DespawnOnCollisionSystem,
+            "despawn_on_collision_system",
+            &[],
+        );
+    }
 }
 
 pub fn update(world: &mut World) -> GameResult<()> {
     let entities = world.entities();
-    let mut despawn_events = world.write_resource::<DespawnEventQueue>();
-    for despawn_event in &despawn_events.0 {
+    let despawn_events = world.read_resource::<DespawnEventQueue>();
+    for despawn_event in despawn_events.read("despawn") {
         if let Err(err) = entities.delete(despawn_event.entity) {
             return Err(GameError::UnknownError(format!(
                 "Failed to delete despawning entity {:?} - {:?}",
@@ -30,7 +50,6 @@ pub fn update(world: &mut World) -> GameResult<()> {
             )));
         }
     }
-    despawn_events.0.clear();
     Ok(())
 }
 
@@ -50,25 +69,11 @@ pub struct DespawnEvent {
     pub reason: DespawnReason,
 }
 
-#[derive(Debug)]
-pub struct DespawnEventQueue(pub Vec<DespawnEvent>);
-impl Default for DespawnEventQueue {
-    fn default() -> DespawnEventQueue {
-        DespawnEventQueue(Vec::new())
-    }
-}
-impl Deref for DespawnEventQueue {
-    type Target = Vec<DespawnEvent>;
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-impl DespawnEventQueue {
-    pub fn new() -> DespawnEventQueue {
-        Default::default()
-    }
+pub type DespawnEventQueue = EventChannel<DespawnEvent>;
+
+impl EventChannel<DespawnEvent> {
     pub fn despawn(&mut self, entity: Entity, reason: DespawnReason) {
-        self.0.push(DespawnEvent { entity, reason });
+        self.single_write(DespawnEvent { entity, reason });
     }
 }
 
@@ -85,13 +90,24 @@ impl<'a> System<'a> for DespawnBoundsSystem {
 
     fn run(&mut self, data: Self::SystemData) {
         let (entities, mut despawn_events, positions, bounds) = data;
-        for (entity, pos, bounds) in (&*entities, &positions, &bounds).join() {
-            let bounds = bounds.0;
-            if pos.x < bounds.x || pos.x > bounds.x + bounds.w || pos.y < bounds.y
-                || pos.y > bounds.y + bounds.h
-            {
-                despawn_events.despawn(entity, DespawnReason::OutOfBounds);
-            }
+        // Collected in entity order, so despawns come out the same however the work was split.
+        let out_of_bounds: Vec<Entity> = (&*entities, &positions, &bounds)
+            .par_join()
+            .filter_map(|(entity, pos, bounds)| {
+                let bounds = bounds.0;
+                if pos.x < bounds.x
+                    || pos.x > bounds.x + bounds.w
+                    || pos.y < bounds.y
+                    || pos.y > bounds.y + bounds.h
+                {
+                    Some(entity)
+                } else {
+                    None
+                }
+            })
+            .collect();
+        for entity in out_of_bounds {
+            despawn_events.despawn(entity, DespawnReason::OutOfBounds);
         }
     }
 }
@@ -0,0 +1,189 @@
+use std::collections::{HashMap, VecDeque};
+use std::collections::vec_deque::Iter;
+use std::iter::Skip;
+use std::sync::Mutex;
+
+// Past this many buffered events the oldest are dropped, even if a reader hasn't seen them yet,
+// so a reader that stops reading (e.g. sounds in a headless run) can't hold on to everything.
+const MAX_BUFFERED: usize = 4096;
+
+// Events written by any number of systems and read by any number of named readers. Each reader
+// sees every event exactly once, whatever order the systems run in - anything written after it
+// last read shows up the next time it reads. Register readers in plugin setup, so they don't
+// miss events written before they first run.
+//
+// Events are dropped once every registered reader has seen them.
+pub struct EventChannel<T> {
+    events: VecDeque<T>,
+    // Number of events dropped so far, i.e. the position of the first buffered event.
+    offset: usize,
+    // Position of each reader's next unread event. Behind a lock so reading only needs a Fetch.
+    readers: Mutex<HashMap<&'static str, usize>>,
+}
+impl<T> EventChannel<T> {
+    pub fn new() -> EventChannel<T> {
+        EventChannel {
+            events: VecDeque::new(),
+            offset: 0,
+            readers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // The reader starts with the next event written.
+    pub fn register_reader(&mut self, reader: &'static str) {
+        let end = self.end();
+        self.readers.get_mut().unwrap().insert(reader, end);
+    }
+
+    pub fn single_write(&mut self, event: T) {
+        self.events.push_back(event);
+        self.trim();
+    }
+
+    pub fn iter_write<I: IntoIterator<Item = T>>(&mut self, events: I) {
+        self.events.extend(events);
+        self.trim();
+    }
+
+    // Drops everything buffered, so no reader sees anything written before now.
+    pub fn clear(&mut self) {
+        self.offset += self.events.len();
+        self.events.clear();
+        let end = self.offset;
+        for next in self.readers.get_mut().unwrap().values_mut() {
+            *next = end;
+        }
+    }
+
+    // Everything written since this reader last read.
+    pub fn read<'a>(&'a self, reader: &'static str) -> Skip<Iter<'a, T>> {
+        let end = self.end();
+        let mut readers = self.readers.lock().unwrap();
+        let next = match readers.get_mut(reader) {
+            Some(next) => next,
+            None => panic!("{} is not a registered event reader", reader),
+        };
+        // A reader that fell too far behind has lost the events dropped in the meantime.
+        let start = next.saturating_sub(self.offset);
+        *next = end;
+        self.events.iter().skip(start)
+    }
+
+    fn end(&self) -> usize {
+        self.offset + self.events.len()
+    }
+
+    fn trim(&mut self) {
+        let end = self.end();
+        let oldest_unread = self
+            .readers
+            .get_mut()
+            .unwrap()
+            .values()
+            .cloned()
+            .min()
+            .unwrap_or(end);
+        let mut seen = oldest_unread.saturating_sub(self.offset);
+        if self.events.len() - seen > MAX_BUFFERED {
+            seen = self.events.len() - MAX_BUFFERED;
+        }
+        self.events.drain(..seen);
+        self.offset += seen;
+    }
+}
+impl<T> Default for EventChannel<T> {
+    fn default() -> EventChannel<T> {
+        EventChannel::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(channel: &EventChannel<usize>, reader: &'static str) -> Vec<usize> {
+        channel.read(reader).cloned().collect()
+    }
+
+    #[test]
+    fn each_reader_sees_each_event_once() {
+        let mut channel = EventChannel::new();
+        channel.register_reader("a");
+        channel.register_reader("b");
+        channel.iter_write(vec![1, 2]);
+        assert_eq!(read(&channel, "a"), vec![1, 2]);
+        channel.single_write(3);
+        assert_eq!(read(&channel, "a"), vec![3]);
+        assert_eq!(read(&channel, "a"), Vec::<usize>::new());
+        assert_eq!(read(&channel, "b"), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn late_readers_start_at_the_end() {
+        let mut channel = EventChannel::new();
+        channel.register_reader("early");
+        channel.single_write(1);
+        channel.register_reader("late");
+        channel.single_write(2);
+        assert_eq!(read(&channel, "late"), vec![2]);
+        assert_eq!(read(&channel, "early"), vec![1, 2]);
+
+        // Registering again skips whatever was left unread.
+        channel.single_write(3);
+        channel.register_reader("early");
+        channel.single_write(4);
+        assert_eq!(read(&channel, "early"), vec![4]);
+    }
+
+    #[test]
+    fn drops_what_everyone_has_seen() {
+        let mut channel = EventChannel::new();
+        channel.register_reader("a");
+        channel.register_reader("b");
+        channel.iter_write(vec![1, 2]);
+        read(&channel, "a");
+        channel.single_write(3);
+        assert_eq!(channel.events.len(), 3);
+        read(&channel, "b");
+        channel.single_write(4);
+        assert_eq!(channel.events.len(), 2);
+        assert_eq!(read(&channel, "a"), vec![3, 4]);
+    }
+
+    #[test]
+    fn trims_past_max_buffered() {
+        let mut channel = EventChannel::new();
+        channel.register_reader("reading");
+        channel.register_reader("stalled");
+        for event in 0..MAX_BUFFERED + 10 {
+            channel.single_write(event);
+            read(&channel, "reading");
+        }
+        assert_eq!(channel.events.len(), MAX_BUFFERED);
+        // The stalled reader lost the oldest ten.
+        let seen = read(&channel, "stalled");
+        assert_eq!(seen.len(), MAX_BUFFERED);
+        assert_eq!(seen[0], 10);
+        assert_eq!(read(&channel, "reading"), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn clear_skips_every_reader_ahead() {
+        let mut channel = EventChannel::new();
+        channel.register_reader("a");
+        channel.register_reader("b");
+        channel.iter_write(vec![1, 2]);
+        read(&channel, "a");
+        channel.clear();
+        channel.single_write(3);
+        assert_eq!(read(&channel, "a"), vec![3]);
+        assert_eq!(read(&channel, "b"), vec![3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "not a registered event reader")]
+    fn unregistered_readers_panic() {
+        let channel: EventChannel<usize> = EventChannel::new();
+        let _ = channel.read("nobody");
+    }
+}
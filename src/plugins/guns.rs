@@ -5,26 +5,248 @@ use plugins::*;
 use game::*;
 use game::sound_effects::SoundEffectType;
 
-pub fn init<'a, 'b>(
-    world: &mut World,
-    dispatcher: DispatcherBuilder<'a, 'b>,
-) -> DispatcherBuilder<'a, 'b> {
-    world.register::<Gun>();
-    dispatcher.add(GunSystem, "gun", &[])
+pub struct GunsPlugin;
+impl Plugin for GunsPlugin {
+    fn name(&self) -> &'static str {
+        "guns"
+    }
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec![
+            "position_motion",
+            "metadata",
+            "collision",
+            "health_damage",
+            "despawn",
+            "sprites",
+            "sound_effects",
+            "viewport",
+        ]
+    }
+    fn setup(&self, world: &mut World) {
+        world.register::<Gun>();
+    }
+    fn systems<'a, 'b>(&self, systems: &mut SystemRegistry<'a, 'b>) {
+        systems.add(Stage::Motion, GunSystem, "gun", &["position_bounds"]);
+    }
+}
+
+// How a weapon fires. Weapons are listed in the game config, so new ones don't need code.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WeaponDef {
+    pub name: String,
+    // Seconds between bursts
+    pub period: f32,
+    pub speed: f32,
+    // Projectiles per shot from each hardpoint, fanned out spread_angle apart
+    pub spread: usize,
+    pub spread_angle: f32,
+    // Shots per pull of the trigger, burst_period seconds apart
+    pub burst: usize,
+    pub burst_period: f32,
+    // Each shot adds heat_per_shot; at max_heat the weapon won't fire again until it's cooled
+    // right down.
+    pub heat_per_shot: f32,
+    pub max_heat: f32,
+    pub cooling_per_second: f32,
+    // Shots before it runs dry. Leave out for a weapon that never does.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ammo: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sound: Option<SoundEffectType>,
+    pub projectile: ProjectileDef,
+    // Where the shots come from, relative to the firing entity and turned with it.
+    pub hardpoints: Vec<Hardpoint>,
+}
+impl Default for WeaponDef {
+    fn default() -> WeaponDef {
+        WeaponDef {
+            name: String::from("Blaster"),
+            period: 0.2,
+            speed: 800.0,
+            spread: 1,
+            spread_angle: 0.15,
+            burst: 1,
+            burst_period: 0.0,
+            heat_per_shot: 0.0,
+            max_heat: 100.0,
+            cooling_per_second: 0.0,
+            ammo: None,
+            sound: Some(SoundEffectType::Shot),
+            projectile: ProjectileDef::default(),
+            hardpoints: vec![Hardpoint {
+                x: 0.0,
+                y: -50.0,
+                angle: 0.0,
+            }],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Hardpoint {
+    pub x: f32,
+    pub y: f32,
+    pub angle: f32,
+}
+
+// What a weapon fires.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProjectileDef {
+    // What it counts as when it hits something, e.g. player_bullet
+    pub tags: Vec<String>,
+    pub health: f32,
+    // Collision layer it's on, and the layers it hits, as bit masks
+    pub layer: u32,
+    pub mask: u32,
+    pub shape: sprites::Shape,
+    pub size: f32,
+    // Checked for hits along the way, not just where it ends up each tick
+    pub fast: bool,
+    // Seconds before it's gone if it hasn't hit anything. Leave out to keep going until it's off
+    // the playfield.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lifetime: Option<f32>,
+    // How far past the edge of the playfield it gets before it's gone
+    pub bounds_margin: f32,
+}
+impl Default for ProjectileDef {
+    fn default() -> ProjectileDef {
+        ProjectileDef {
+            tags: vec![String::from("player_bullet"), String::from("player_weapon")],
+            health: 10.0,
+            layer: collision::LAYER_PLAYER_BULLET,
+            mask: collision::LAYER_ASTEROID,
+            shape: sprites::Shape::SimpleBullet,
+            size: 50.0,
+            fast: true,
+            lifetime: None,
+            bounds_margin: 100.0,
+        }
+    }
+}
+impl ProjectileDef {
+    // Everything about the projectile that doesn't depend on how it was fired.
+    fn spawn(
+        &self,
+        entity: Entity,
+        lazy: &LazyUpdate,
+        strings: &mut metadata::Strings,
+        viewport: &viewport::ViewportState,
+    ) {
+        let tags = self.tags.iter().map(|tag| strings.intern(tag)).collect();
+        lazy.insert(entity, metadata::Tags::new(tags));
+        let sprite = sprites::Sprite {
+            shape: self.shape,
+            scale: Point2::new(self.size, self.size),
+            ..Default::default()
+        };
+        lazy.insert(
+            entity,
+            collision::Collidable::from_sprite(&sprite).with_layer(self.layer, self.mask),
+        );
+        if self.fast {
+            lazy.insert(entity, collision::FastMover);
+        }
+        lazy.insert(entity, health_damage::Health::new(self.health));
+        if let Some(lifetime) = self.lifetime {
+            lazy.insert(entity, despawn::Timeout(lifetime));
+        }
+        let (width, height) = (viewport.playfield_width, viewport.playfield_height);
+        let margin = self.bounds_margin;
+        lazy.insert(
+            entity,
+            despawn::DespawnBounds(Rect::new(
+                -width / 2.0 - margin,
+                -height / 2.0 - margin,
+                width + margin * 2.0,
+                height + margin * 2.0,
+            )),
+        );
+        lazy.insert(entity, sprite);
+    }
+}
+
+// A weapon being carried, and how ready it is to fire.
+#[derive(Clone, Debug)]
+pub struct Weapon {
+    pub def: WeaponDef,
+    pub cooldown: f32,
+    pub heat: f32,
+    pub overheated: bool,
+    pub ammo: Option<u32>,
+    // Shots left in the burst under way
+    pub burst_left: usize,
+}
+impl Weapon {
+    pub fn new(def: WeaponDef) -> Weapon {
+        Weapon {
+            ammo: def.ammo,
+            def,
+            cooldown: 0.0,
+            heat: 0.0,
+            overheated: false,
+            burst_left: 0,
+        }
+    }
+
+    pub fn can_fire(&self) -> bool {
+        !self.overheated && self.ammo != Some(0)
+    }
+
+    fn cool(&mut self, delta: f32) {
+        if self.cooldown > 0.0 {
+            self.cooldown -= delta;
+        }
+        self.heat = (self.heat - self.def.cooling_per_second * delta).max(0.0);
+        if self.heat <= 0.0 {
+            self.overheated = false;
+        }
+    }
 }
 
 #[derive(Component, Debug)]
 pub struct Gun {
     pub firing: bool,
-    pub period: f32,
-    pub cooldown: f32,
+    pub weapons: Vec<Weapon>,
+    pub selected: usize,
+    // Power-ups change every weapon at once: time between bursts is multiplied by period_scale,
+    // and each shot fans out extra_spread more projectiles.
+    pub period_scale: f32,
+    pub extra_spread: usize,
+}
+impl Gun {
+    pub fn new(weapons: Vec<WeaponDef>) -> Gun {
+        Gun {
+            weapons: weapons.into_iter().map(Weapon::new).collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn weapon(&self) -> Option<&Weapon> {
+        self.weapons.get(self.selected)
+    }
+
+    pub fn next_weapon(&mut self) {
+        if !self.weapons.is_empty() {
+            self.selected = (self.selected + 1) % self.weapons.len();
+        }
+        // Don't finish a burst from the weapon just put away.
+        for weapon in &mut self.weapons {
+            weapon.burst_left = 0;
+        }
+    }
 }
 impl Default for Gun {
     fn default() -> Gun {
         Gun {
             firing: false,
-            period: 1.0,
-            cooldown: 0.0,
+            weapons: vec![],
+            selected: 0,
+            period_scale: 1.0,
+            extra_spread: 0,
         }
     }
 }
@@ -36,60 +258,93 @@ impl<'a> System<'a> for GunSystem {
         Entities<'a>,
         Fetch<'a, DeltaTime>,
         Fetch<'a, LazyUpdate>,
+        Fetch<'a, viewport::ViewportState>,
         FetchMut<'a, sound_effects::SoundEffectQueue>,
+        FetchMut<'a, metadata::Strings>,
         ReadStorage<'a, position_motion::Position>,
+        ReadStorage<'a, health_damage::Owner>,
         WriteStorage<'a, Gun>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (entities, delta, lazy, mut sounds, positions, mut guns) = data;
+        let (entities, delta, lazy, viewport, mut sounds, mut strings, positions, owners, mut guns) =
+            data;
         let delta = delta.0;
-        for (_entity, position, gun) in (&*entities, &positions, &mut guns).join() {
-            if gun.cooldown > 0.0 {
-                gun.cooldown -= delta;
+        for (entity, position, gun) in (&*entities, &positions, &mut guns).join() {
+            // Weapons cool down whether they're in hand or not.
+            for weapon in &mut gun.weapons {
+                weapon.cool(delta);
+            }
+            let (firing, period_scale, extra_spread) =
+                (gun.firing, gun.period_scale, gun.extra_spread);
+            let weapon = match gun.weapons.get_mut(gun.selected) {
+                Some(weapon) => weapon,
+                None => continue,
+            };
+            if weapon.cooldown > 0.0 {
                 continue;
             }
-            if !gun.firing {
+            if weapon.burst_left == 0 {
+                if !firing {
+                    continue;
+                }
+                weapon.burst_left = weapon.def.burst.max(1);
+            }
+            if !weapon.can_fire() {
+                weapon.burst_left = 0;
                 continue;
             }
-            gun.cooldown = gun.period;
 
-            sounds.play(SoundEffectType::Shot);
+            weapon.burst_left -= 1;
+            weapon.cooldown = if weapon.burst_left > 0 {
+                weapon.def.burst_period
+            } else {
+                weapon.def.period * period_scale
+            };
+            if weapon.def.heat_per_shot > 0.0 {
+                weapon.heat += weapon.def.heat_per_shot;
+                if weapon.heat >= weapon.def.max_heat {
+                    weapon.overheated = true;
+                }
+            }
+            if let Some(ref mut ammo) = weapon.ammo {
+                *ammo -= 1;
+            }
+            if let Some(sound) = weapon.def.sound {
+                sounds.play(sound);
+            }
 
-            let bullet = entities.create();
-            lazy.insert(
-                bullet,
-                metadata::Tags::new(vec!["player_bullet", "player_weapon"]),
-            );
-            lazy.insert(
-                bullet,
-                position_motion::Position {
-                    x: position.x,
-                    y: position.y - 50.0,
-                    ..Default::default()
-                },
-            );
-            lazy.insert(
-                bullet,
-                position_motion::Velocity {
-                    y: -800.0,
-                    ..Default::default()
-                },
-            );
-            lazy.insert(bullet, collision::Collidable { size: 50.0 });
-            lazy.insert(bullet, health_damage::Health::new(10.0));
-            lazy.insert(
-                bullet,
-                despawn::DespawnBounds(Rect::new(-800.0, -550.0, 1600.0, 1000.0)),
-            );
-            lazy.insert(
-                bullet,
-                sprites::Sprite {
-                    shape: sprites::Shape::SimpleBullet,
-                    scale: Point2::new(50.0, 50.0),
-                    ..Default::default()
-                },
-            );
+            // A gun that's itself owned, e.g. a turret, passes the credit on up.
+            let owner = owners.get(entity).map_or(entity, |owner| owner.0);
+            let def = &weapon.def;
+            let shots = def.spread.max(1) + extra_spread;
+            let (sin, cos) = position.r.sin_cos();
+            for hardpoint in &def.hardpoints {
+                for shot in 0..shots {
+                    let fan = def.spread_angle * (shot as f32 - (shots - 1) as f32 / 2.0);
+                    let angle = position.r + hardpoint.angle + fan;
+                    let projectile = entities.create();
+                    def.projectile
+                        .spawn(projectile, &lazy, &mut strings, &viewport);
+                    lazy.insert(
+                        projectile,
+                        position_motion::Position {
+                            x: position.x + hardpoint.x * cos - hardpoint.y * sin,
+                            y: position.y + hardpoint.x * sin + hardpoint.y * cos,
+                            r: angle,
+                        },
+                    );
+                    lazy.insert(
+                        projectile,
+                        position_motion::Velocity {
+                            x: def.speed * angle.sin(),
+                            y: -def.speed * angle.cos(),
+                            ..Default::default()
+                        },
+                    );
+                    lazy.insert(projectile, health_damage::Owner(owner));
+                }
+            }
         }
     }
 }
@@ -1,25 +1,53 @@
 use specs::*;
 use plugins::*;
-use std::ops::Deref;
+use plugins::events::EventChannel;
 
-pub fn init<'a, 'b>(
-    world: &mut World,
-    dispatcher: DispatcherBuilder<'a, 'b>,
-) -> DispatcherBuilder<'a, 'b> {
-    world.add_resource(DamageEventQueue::new());
-    world.register::<Health>();
-    world.register::<DamageOnCollision>();
-    dispatcher
-        .add(DamageOnCollisionSystem, "damage_on_collision", &[])
-        .add(HealthSystem, "health", &["damage_on_collision"])
+pub struct HealthDamagePlugin;
+impl Plugin for HealthDamagePlugin {
+    fn name(&self) -> &'static str {
+        "health_damage"
+    }
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec!["collision", "despawn"]
+    }
+    fn setup(&self, world: &mut World) {
+        let mut damage_events = DamageEventQueue::new();
+        damage_events.register_reader("health");
+        world.add_resource(damage_events);
+        world.register::<Health>();
+        world.register::<DamageOnCollision>();
+        world.register::<Owner>();
+    }
+    fn systems<'a, 'b>(&self, systems: &mut SystemRegistry<'a, 'b>) {
+        systems.add(
+            Stage::Damage,
+            DamageOnCollisionSystem,
+            "damage_on_collision",
+            &[],
+        );
+        systems.add(
+            Stage::Damage,
+            HealthSystem,
+            "health",
+            &["damage_on_collision"],
+        );
+    }
 }
 
+// Whoever gets the credit for damage this does, e.g. the ship that fired a bullet. Damage from
+// anything without one is credited to the thing itself.
+#[derive(Component, Debug)]
+pub struct Owner(pub Entity);
+
 #[derive(Component, Debug)]
 pub struct Health {
     pub health: f32,
     pub max_health: f32,
+    // Who gets the kill if this is what finishes it off
     pub last_hurt_by: Option<Entity>,
     pub last_healed_by: Option<Entity>,
+    // Total damage taken from each attacker, by owner
+    pub attackers: Vec<(Entity, f32)>,
 }
 impl Health {
     pub fn new(health: f32) -> Health {
@@ -32,11 +60,27 @@ impl Health {
     pub fn hurt(&mut self, amount: f32, from: Entity) {
         self.health -= amount;
         self.last_hurt_by = Some(from);
+        match self
+            .attackers
+            .iter()
+            .position(|&(attacker, _)| attacker == from)
+        {
+            Some(idx) => self.attackers[idx].1 += amount,
+            None => self.attackers.push((from, amount)),
+        }
     }
     pub fn heal(&mut self, amount: f32, from: Entity) {
         self.health += amount;
         self.last_healed_by = Some(from);
     }
+    // Everyone who did some damage except whoever gets the kill.
+    pub fn assists(&self) -> Vec<Entity> {
+        self.attackers
+            .iter()
+            .map(|&(attacker, _)| attacker)
+            .filter(|&attacker| Some(attacker) != self.last_hurt_by)
+            .collect()
+    }
 }
 impl Default for Health {
     fn default() -> Health {
@@ -45,6 +89,7 @@ impl Default for Health {
             max_health: 100.0,
             last_hurt_by: None,
             last_healed_by: None,
+            attackers: Vec::new(),
         }
     }
 }
@@ -78,25 +123,11 @@ pub struct DamageEvent {
     pub polarity: DamagePolarity,
 }
 
-#[derive(Debug)]
-pub struct DamageEventQueue(pub Vec<DamageEvent>);
-impl Default for DamageEventQueue {
-    fn default() -> DamageEventQueue {
-        DamageEventQueue(Vec::new())
-    }
-}
-impl Deref for DamageEventQueue {
-    type Target = Vec<DamageEvent>;
-    fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-impl DamageEventQueue {
-    pub fn new() -> DamageEventQueue {
-        Default::default()
-    }
+pub type DamageEventQueue = EventChannel<DamageEvent>;
+
+impl EventChannel<DamageEvent> {
     pub fn hurt(&mut self, from: Entity, to: Entity, amount: f32) {
-        self.0.push(DamageEvent {
+        self.single_write(DamageEvent {
             from,
             to,
             amount,
@@ -108,7 +139,7 @@ impl DamageEventQueue {
         self.hurt(b, a, amount);
     }
     pub fn heal(&mut self, from: Entity, to: Entity, amount: f32) {
-        self.0.push(DamageEvent {
+        self.single_write(DamageEvent {
             from,
             to,
             amount: 0.0 - amount,
@@ -152,19 +183,27 @@ pub struct HealthSystem;
 impl<'a> System<'a> for HealthSystem {
     type SystemData = (
         Entities<'a>,
-        FetchMut<'a, DamageEventQueue>,
+        Fetch<'a, DamageEventQueue>,
         FetchMut<'a, despawn::DespawnEventQueue>,
+        ReadStorage<'a, Owner>,
         WriteStorage<'a, Health>,
     );
     fn run(&mut self, data: Self::SystemData) {
-        let (entities, mut damage_events, mut despawn_events, mut healths) = data;
+        let (entities, damage_events, mut despawn_events, owners, mut healths) = data;
         // TODO: Maintain a timer to ignore repeated damage from a source for a period of time
-        for damage_event in &damage_events.0 {
+        for damage_event in damage_events.read("health") {
+            // Worked out now, while whatever did the damage is still around - a bullet is gone
+            // by the time anything is scored.
+            let from = owners
+                .get(damage_event.from)
+                .map_or(damage_event.from, |owner| owner.0);
             if let Some(ref mut health) = healths.get_mut(damage_event.to) {
-                health.hurt(damage_event.amount, damage_event.from);
+                match damage_event.polarity {
+                    DamagePolarity::Hurt => health.hurt(damage_event.amount, from),
+                    DamagePolarity::Heal => health.heal(-damage_event.amount, from),
+                }
             }
         }
-        damage_events.0.clear();
         for (entity, health) in (&*entities, &mut healths).join() {
             if health.health <= 0.0 {
                 despawn_events.despawn(entity, despawn::DespawnReason::Health);
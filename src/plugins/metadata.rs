@@ -1,13 +1,18 @@
 use specs::*;
+use std::collections::HashSet;
 use std::ops::Deref;
+use plugins::Plugin;
 
-pub fn init<'a, 'b>(
-    world: &mut World,
-    dispatcher: DispatcherBuilder<'a, 'b>,
-) -> DispatcherBuilder<'a, 'b> {
-    world.register::<Name>();
-    world.register::<Tags>();
-    dispatcher
+pub struct MetadataPlugin;
+impl Plugin for MetadataPlugin {
+    fn name(&self) -> &'static str {
+        "metadata"
+    }
+    fn setup(&self, world: &mut World) {
+        world.register::<Name>();
+        world.register::<Tags>();
+        world.add_resource(Strings::new());
+    }
 }
 
 #[derive(Component)]
@@ -28,3 +33,26 @@ impl Deref for Tags {
         &self.0
     }
 }
+
+// Names and tags are &'static str, so ones that come from a file - a snapshot, or the game
+// config - have to live forever. Keep each one around so using the same name again doesn't
+// leak more.
+pub struct Strings(HashSet<&'static str>);
+impl Strings {
+    pub fn new() -> Strings {
+        Strings(HashSet::new())
+    }
+    pub fn intern(&mut self, value: &str) -> &'static str {
+        if let Some(existing) = self.0.get(value) {
+            return existing;
+        }
+        let leaked: &'static str = Box::leak(value.to_owned().into_boxed_str());
+        self.0.insert(leaked);
+        leaked
+    }
+}
+impl Default for Strings {
+    fn default() -> Strings {
+        Strings::new()
+    }
+}
@@ -1,14 +1,632 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use specs::*;
+use ggez::{GameError, GameResult};
+
 pub mod bounce;
 pub mod collision;
 pub mod despawn;
+pub mod events;
 pub mod fonts;
 pub mod guns;
 pub mod health_damage;
 pub mod metadata;
 pub mod player_control;
 pub mod position_motion;
+pub mod profiler;
+pub mod replay;
+pub mod rng;
 pub mod score;
 pub mod simple_physics;
 pub mod sprites;
 pub mod thruster;
 pub mod viewport;
+
+// Coarse phases of a tick. Every system in a stage finishes before any system in a later stage
+// starts, so e.g. damage always sees this tick's collisions.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Stage {
+    Input,
+    Physics,
+    Motion,
+    Collision,
+    Damage,
+    Despawn,
+    Game,
+}
+pub const STAGES: [Stage; 7] = [
+    Stage::Input,
+    Stage::Physics,
+    Stage::Motion,
+    Stage::Collision,
+    Stage::Damage,
+    Stage::Despawn,
+    Stage::Game,
+];
+impl Stage {
+    // Input and game mode logic keep going while the simulation is paused, so menus still work.
+    pub fn runs_while_paused(&self) -> bool {
+        *self == Stage::Input || *self == Stage::Game
+    }
+}
+
+pub trait Plugin {
+    // Unique name, used by other plugins to declare a dependency on this one.
+    fn name(&self) -> &'static str;
+
+    // Plugins whose resources, components, or systems this one relies on.
+    fn dependencies(&self) -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    // Add resources and register components.
+    fn setup(&self, _world: &mut World) {}
+
+    // Declare systems along with the stage they run in and their ordering constraints.
+    fn systems<'a, 'b>(&self, _systems: &mut SystemRegistry<'a, 'b>) {}
+
+    // Called once per tick after the systems have run, with the whole world to hand. Runs even
+    // while paused.
+    fn update(&self, _world: &mut World) -> GameResult<()> {
+        Ok(())
+    }
+}
+
+type AddSystem<'a, 'b> =
+    Box<dyn FnOnce(DispatcherBuilder<'a, 'b>, &[&str]) -> DispatcherBuilder<'a, 'b> + 'a>;
+
+struct SystemEntry<'a, 'b> {
+    plugin: &'static str,
+    name: &'static str,
+    stage: Stage,
+    dependencies: Vec<&'static str>,
+    add: AddSystem<'a, 'b>,
+}
+
+pub struct SystemRegistry<'a, 'b> {
+    plugin: &'static str,
+    entries: Vec<SystemEntry<'a, 'b>>,
+}
+impl<'a, 'b> SystemRegistry<'a, 'b> {
+    fn new() -> SystemRegistry<'a, 'b> {
+        SystemRegistry {
+            plugin: "",
+            entries: Vec::new(),
+        }
+    }
+
+    // Like DispatcherBuilder::add, but with a stage. Dependencies can name any system in the
+    // same or an earlier stage.
+    pub fn add<T>(&mut self, stage: Stage, system: T, name: &'static str, deps: &[&'static str])
+    where
+        T: for<'c> System<'c> + Send + 'a,
+    {
+        self.entries.push(SystemEntry {
+            plugin: self.plugin,
+            name,
+            stage,
+            dependencies: deps.to_vec(),
+            add: Box::new(
+                move |dispatcher: DispatcherBuilder<'a, 'b>, deps: &[&str]| {
+                    dispatcher.add(profiler::Profiled { name, system }, name, deps)
+                },
+            ),
+        });
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PluginError {
+    DuplicatePlugin(&'static str),
+    MissingPlugin {
+        plugin: &'static str,
+        dependency: &'static str,
+    },
+    PluginCycle(Vec<&'static str>),
+    DuplicateSystem(&'static str),
+    MissingSystem {
+        plugin: &'static str,
+        system: &'static str,
+        dependency: &'static str,
+    },
+    SystemOutOfOrder {
+        system: &'static str,
+        dependency: &'static str,
+    },
+    SystemCycle(Vec<&'static str>),
+}
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PluginError::DuplicatePlugin(name) => {
+                write!(f, "plugin \"{}\" was added more than once", name)
+            }
+            PluginError::MissingPlugin { plugin, dependency } => write!(
+                f,
+                "plugin \"{}\" depends on missing plugin \"{}\"",
+                plugin, dependency
+            ),
+            PluginError::PluginCycle(ref names) => {
+                write!(f, "plugin dependency cycle among {:?}", names)
+            }
+            PluginError::DuplicateSystem(name) => {
+                write!(f, "system \"{}\" was added more than once", name)
+            }
+            PluginError::MissingSystem {
+                plugin,
+                system,
+                dependency,
+            } => write!(
+                f,
+                "system \"{}\" from plugin \"{}\" depends on missing system \"{}\"",
+                system, plugin, dependency
+            ),
+            PluginError::SystemOutOfOrder { system, dependency } => write!(
+                f,
+                "system \"{}\" depends on \"{}\", which runs in a later stage",
+                system, dependency
+            ),
+            PluginError::SystemCycle(ref names) => {
+                write!(f, "system dependency cycle among {:?}", names)
+            }
+        }
+    }
+}
+impl Error for PluginError {
+    fn description(&self) -> &str {
+        "plugin set could not be resolved"
+    }
+}
+impl From<PluginError> for GameError {
+    fn from(err: PluginError) -> GameError {
+        GameError::UnknownError(format!("{}", err))
+    }
+}
+
+// An unordered collection of plugins, resolved into a World and DispatcherBuilder by build().
+pub struct PluginSet {
+    plugins: Vec<Box<dyn Plugin>>,
+    // Indexes into plugins, in dependency order. Filled in when the set is built.
+    order: Vec<usize>,
+}
+impl PluginSet {
+    pub fn new() -> PluginSet {
+        PluginSet {
+            plugins: Vec::new(),
+            order: Vec::new(),
+        }
+    }
+
+    pub fn with<P: Plugin + 'static>(mut self, plugin: P) -> PluginSet {
+        self.plugins.push(Box::new(plugin));
+        self
+    }
+
+    // Builds a single dispatcher, with a barrier between each stage.
+    pub fn build<'a, 'b>(
+        &mut self,
+        world: &mut World,
+    ) -> Result<DispatcherBuilder<'a, 'b>, PluginError> {
+        let mut dispatcher = DispatcherBuilder::new();
+        for (_stage, systems) in self.resolve_systems(world)? {
+            dispatcher = add_systems(dispatcher, systems);
+            dispatcher = dispatcher.add_barrier();
+        }
+        Ok(dispatcher)
+    }
+
+    // Builds a dispatcher per stage, so that stages can be skipped - e.g. while paused.
+    pub fn build_staged<'a, 'b>(
+        &mut self,
+        world: &mut World,
+    ) -> Result<StagedDispatcher<'a, 'b>, PluginError> {
+        let stages = self
+            .resolve_systems(world)?
+            .into_iter()
+            .map(|(stage, systems)| (stage, add_systems(DispatcherBuilder::new(), systems).build()))
+            .collect();
+        Ok(StagedDispatcher { stages })
+    }
+
+    // Sets up every plugin, then groups their systems by stage and sorts each stage.
+    fn resolve_systems<'a, 'b>(
+        &mut self,
+        world: &mut World,
+    ) -> Result<Vec<(Stage, Vec<StagedSystem<'a, 'b>>)>, PluginError> {
+        self.order = self.resolve_plugins()?;
+
+        // Every system gets timed through this, so it's there whichever plugins are used.
+        world.add_resource(profiler::Profiler::new());
+
+        let mut systems = SystemRegistry::new();
+        for &idx in &self.order {
+            let plugin = &self.plugins[idx];
+            plugin.setup(world);
+            systems.plugin = plugin.name();
+            plugin.systems(&mut systems);
+        }
+
+        let mut stages: HashMap<&'static str, Stage> = HashMap::new();
+        for entry in &systems.entries {
+            if stages.insert(entry.name, entry.stage).is_some() {
+                return Err(PluginError::DuplicateSystem(entry.name));
+            }
+        }
+        for entry in &systems.entries {
+            for &dependency in &entry.dependencies {
+                match stages.get(dependency) {
+                    None => {
+                        return Err(PluginError::MissingSystem {
+                            plugin: entry.plugin,
+                            system: entry.name,
+                            dependency,
+                        })
+                    }
+                    Some(&stage) if stage > entry.stage => {
+                        return Err(PluginError::SystemOutOfOrder {
+                            system: entry.name,
+                            dependency,
+                        })
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        // Stages run one after another, which takes care of cross-stage ordering, so only
+        // same-stage dependencies need sorting. Otherwise systems keep the order their plugins
+        // resolved in.
+        let mut resolved = Vec::new();
+        let mut entries = systems.entries;
+        for stage in STAGES.iter() {
+            let (in_stage, rest): (Vec<_>, Vec<_>) =
+                entries.into_iter().partition(|entry| entry.stage == *stage);
+            entries = rest;
+            let staged = sort_stage(in_stage)?
+                .into_iter()
+                .map(|entry| {
+                    let deps = entry
+                        .dependencies
+                        .iter()
+                        .filter(|name| stages.get(*name) == Some(stage))
+                        .cloned()
+                        .collect();
+                    StagedSystem {
+                        deps,
+                        add: entry.add,
+                    }
+                })
+                .collect();
+            resolved.push((*stage, staged));
+        }
+        Ok(resolved)
+    }
+
+    // Orders plugins so each comes after everything it depends on, erroring on missing
+    // dependencies and cycles. Returns indexes into self.plugins.
+    fn resolve_plugins(&self) -> Result<Vec<usize>, PluginError> {
+        let mut names = HashSet::new();
+        for plugin in &self.plugins {
+            if !names.insert(plugin.name()) {
+                return Err(PluginError::DuplicatePlugin(plugin.name()));
+            }
+        }
+        for plugin in &self.plugins {
+            for dependency in plugin.dependencies() {
+                if !names.contains(dependency) {
+                    return Err(PluginError::MissingPlugin {
+                        plugin: plugin.name(),
+                        dependency,
+                    });
+                }
+            }
+        }
+
+        let mut resolved: Vec<usize> = Vec::new();
+        let mut done: HashSet<&'static str> = HashSet::new();
+        let mut pending: Vec<usize> = (0..self.plugins.len()).collect();
+        while !pending.is_empty() {
+            let (ready, waiting): (Vec<usize>, Vec<usize>) =
+                pending.into_iter().partition(|&idx| {
+                    self.plugins[idx]
+                        .dependencies()
+                        .iter()
+                        .all(|d| done.contains(d))
+                });
+            if ready.is_empty() {
+                return Err(PluginError::PluginCycle(
+                    waiting
+                        .iter()
+                        .map(|&idx| self.plugins[idx].name())
+                        .collect(),
+                ));
+            }
+            for idx in ready {
+                done.insert(self.plugins[idx].name());
+                resolved.push(idx);
+            }
+            pending = waiting;
+        }
+        Ok(resolved)
+    }
+}
+impl PluginSet {
+    // Runs every plugin's update hook, each after everything it depends on. Does nothing
+    // until the set has been built.
+    pub fn update(&self, world: &mut World) -> GameResult<()> {
+        for &idx in &self.order {
+            self.plugins[idx].update(world)?;
+        }
+        Ok(())
+    }
+}
+impl Default for PluginSet {
+    fn default() -> PluginSet {
+        PluginSet::new()
+    }
+}
+
+// A system that's been sorted into its stage, along with the dependencies that apply there.
+struct StagedSystem<'a, 'b> {
+    deps: Vec<&'static str>,
+    add: AddSystem<'a, 'b>,
+}
+
+fn add_systems<'a, 'b>(
+    mut dispatcher: DispatcherBuilder<'a, 'b>,
+    systems: Vec<StagedSystem<'a, 'b>>,
+) -> DispatcherBuilder<'a, 'b> {
+    for system in systems {
+        dispatcher = (system.add)(dispatcher, &system.deps);
+    }
+    dispatcher
+}
+
+pub struct StagedDispatcher<'a, 'b> {
+    stages: Vec<(Stage, Dispatcher<'a, 'b>)>,
+}
+impl<'a, 'b> StagedDispatcher<'a, 'b> {
+    pub fn dispatch(&mut self, world: &World) {
+        self.dispatch_stages(world, |_| true);
+    }
+
+    // Runs only the stages for which `filter` returns true, in order.
+    pub fn dispatch_stages<F: Fn(Stage) -> bool>(&mut self, world: &World, filter: F) {
+        for &mut (stage, ref mut dispatcher) in &mut self.stages {
+            if filter(stage) {
+                dispatcher.dispatch(&world.res);
+            }
+        }
+    }
+}
+
+fn sort_stage<'a, 'b>(
+    entries: Vec<SystemEntry<'a, 'b>>,
+) -> Result<Vec<SystemEntry<'a, 'b>>, PluginError> {
+    let in_stage: HashSet<&'static str> = entries.iter().map(|entry| entry.name).collect();
+    let mut sorted = Vec::new();
+    let mut done: HashSet<&'static str> = HashSet::new();
+    let mut pending = entries;
+    while !pending.is_empty() {
+        let (ready, waiting): (Vec<_>, Vec<_>) = pending.into_iter().partition(|entry| {
+            entry
+                .dependencies
+                .iter()
+                .all(|d| !in_stage.contains(d) || done.contains(d))
+        });
+        if ready.is_empty() {
+            return Err(PluginError::SystemCycle(
+                waiting.iter().map(|entry| entry.name).collect(),
+            ));
+        }
+        for entry in ready {
+            done.insert(entry.name);
+            sorted.push(entry);
+        }
+        pending = waiting;
+    }
+    Ok(sorted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Everything the test plugins and systems did, in order.
+    #[derive(Default)]
+    struct Log(Vec<&'static str>);
+
+    struct Record(&'static str);
+    impl<'a> System<'a> for Record {
+        type SystemData = FetchMut<'a, Log>;
+        fn run(&mut self, mut log: Self::SystemData) {
+            log.0.push(self.0);
+        }
+    }
+
+    struct TestPlugin {
+        name: &'static str,
+        dependencies: Vec<&'static str>,
+        systems: Vec<(Stage, &'static str, Vec<&'static str>)>,
+    }
+    impl Plugin for TestPlugin {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+        fn dependencies(&self) -> Vec<&'static str> {
+            self.dependencies.clone()
+        }
+        fn setup(&self, world: &mut World) {
+            world.write_resource::<Log>().0.push(self.name);
+        }
+        fn systems<'a, 'b>(&self, systems: &mut SystemRegistry<'a, 'b>) {
+            for &(stage, name, ref deps) in &self.systems {
+                systems.add(stage, Record(name), name, deps);
+            }
+        }
+        fn update(&self, world: &mut World) -> GameResult<()> {
+            world.write_resource::<Log>().0.push(self.name);
+            Ok(())
+        }
+    }
+
+    fn plugin(name: &'static str, dependencies: &[&'static str]) -> TestPlugin {
+        TestPlugin {
+            name,
+            dependencies: dependencies.to_vec(),
+            systems: Vec::new(),
+        }
+    }
+
+    fn with_system(
+        mut plugin: TestPlugin,
+        stage: Stage,
+        name: &'static str,
+        deps: &[&'static str],
+    ) -> TestPlugin {
+        plugin.systems.push((stage, name, deps.to_vec()));
+        plugin
+    }
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.add_resource(Log::default());
+        world
+    }
+
+    fn build_error(mut plugins: PluginSet) -> PluginError {
+        match plugins.build_staged(&mut world()) {
+            Ok(_) => panic!("expected the plugin set not to resolve"),
+            Err(e) => e,
+        }
+    }
+
+    fn take_log(world: &World) -> Vec<&'static str> {
+        world.write_resource::<Log>().0.drain(..).collect()
+    }
+
+    #[test]
+    fn plugins_run_after_their_dependencies() {
+        let mut plugins = PluginSet::new()
+            .with(plugin("c", &["b"]))
+            .with(plugin("b", &["a"]))
+            .with(plugin("a", &[]))
+            .with(plugin("d", &[]));
+        let mut world = world();
+        plugins.build_staged(&mut world).unwrap();
+        assert_eq!(take_log(&world), vec!["a", "d", "b", "c"]);
+        plugins.update(&mut world).unwrap();
+        assert_eq!(take_log(&world), vec!["a", "d", "b", "c"]);
+    }
+
+    #[test]
+    fn updates_wait_for_build() {
+        let plugins = PluginSet::new().with(plugin("a", &[]));
+        let mut world = world();
+        plugins.update(&mut world).unwrap();
+        assert!(take_log(&world).is_empty());
+    }
+
+    #[test]
+    fn missing_plugin() {
+        let plugins = PluginSet::new()
+            .with(plugin("a", &[]))
+            .with(plugin("b", &["a", "nope"]));
+        assert_eq!(
+            build_error(plugins),
+            PluginError::MissingPlugin {
+                plugin: "b",
+                dependency: "nope",
+            }
+        );
+    }
+
+    #[test]
+    fn duplicate_plugin() {
+        let plugins = PluginSet::new()
+            .with(plugin("a", &[]))
+            .with(plugin("a", &[]));
+        assert_eq!(build_error(plugins), PluginError::DuplicatePlugin("a"));
+    }
+
+    #[test]
+    fn plugin_cycle() {
+        let plugins = PluginSet::new()
+            .with(plugin("a", &[]))
+            .with(plugin("b", &["a", "c"]))
+            .with(plugin("c", &["b"]));
+        assert_eq!(
+            build_error(plugins),
+            PluginError::PluginCycle(vec!["b", "c"])
+        );
+    }
+
+    #[test]
+    fn systems_run_in_stage_and_dependency_order() {
+        let a = plugin("a", &[]);
+        let a = with_system(a, Stage::Game, "a_game", &["b_physics"]);
+        let a = with_system(a, Stage::Physics, "a_physics", &["b_physics"]);
+        let b = with_system(plugin("b", &["a"]), Stage::Physics, "b_physics", &[]);
+        let b = with_system(b, Stage::Input, "b_input", &[]);
+        let mut plugins = PluginSet::new().with(a).with(b);
+        let mut world = world();
+        let mut dispatcher = plugins.build_staged(&mut world).unwrap();
+        take_log(&world);
+
+        dispatcher.dispatch(&world);
+        assert_eq!(
+            take_log(&world),
+            vec!["b_input", "b_physics", "a_physics", "a_game"]
+        );
+        dispatcher.dispatch_stages(&world, |stage| stage.runs_while_paused());
+        assert_eq!(take_log(&world), vec!["b_input", "a_game"]);
+    }
+
+    #[test]
+    fn duplicate_system() {
+        let a = with_system(plugin("a", &[]), Stage::Game, "same", &[]);
+        let b = with_system(plugin("b", &[]), Stage::Input, "same", &[]);
+        let plugins = PluginSet::new().with(a).with(b);
+        assert_eq!(build_error(plugins), PluginError::DuplicateSystem("same"));
+    }
+
+    #[test]
+    fn missing_system() {
+        let a = with_system(plugin("a", &[]), Stage::Game, "a_game", &["nope"]);
+        assert_eq!(
+            build_error(PluginSet::new().with(a)),
+            PluginError::MissingSystem {
+                plugin: "a",
+                system: "a_game",
+                dependency: "nope",
+            }
+        );
+    }
+
+    #[test]
+    fn system_out_of_order() {
+        let a = plugin("a", &[]);
+        let a = with_system(a, Stage::Input, "a_input", &["a_game"]);
+        let a = with_system(a, Stage::Game, "a_game", &[]);
+        assert_eq!(
+            build_error(PluginSet::new().with(a)),
+            PluginError::SystemOutOfOrder {
+                system: "a_input",
+                dependency: "a_game",
+            }
+        );
+    }
+
+    #[test]
+    fn system_cycle() {
+        let a = plugin("a", &[]);
+        let a = with_system(a, Stage::Game, "first", &["second"]);
+        let a = with_system(a, Stage::Game, "second", &["first"]);
+        let a = with_system(a, Stage::Game, "third", &[]);
+        assert_eq!(
+            build_error(PluginSet::new().with(a)),
+            PluginError::SystemCycle(vec!["first", "second"])
+        );
+    }
+}
@@ -1,14 +1,17 @@
 use ggez::*;
 use ggez::event::{Axis, Button, Keycode, Mod};
 use specs::*;
+use plugins::Plugin;
 
-pub fn init<'a, 'b>(
-    world: &mut World,
-    dispatcher: DispatcherBuilder<'a, 'b>,
-) -> DispatcherBuilder<'a, 'b> {
-    world.register::<PlayerControl>();
-    world.add_resource(Inputs::new());
-    dispatcher
+pub struct PlayerControlPlugin;
+impl Plugin for PlayerControlPlugin {
+    fn name(&self) -> &'static str {
+        "player_control"
+    }
+    fn setup(&self, world: &mut World) {
+        world.register::<PlayerControl>();
+        world.add_resource(Inputs::new());
+    }
 }
 
 #[derive(Component, Debug)]
@@ -22,6 +25,7 @@ pub struct Inputs {
     pub down: bool,
     pub fire: bool,
     pub special: bool,
+    pub pause: bool,
 }
 impl Inputs {
     pub fn new() -> Inputs {
@@ -32,6 +36,7 @@ impl Inputs {
             down: false,
             fire: false,
             special: false,
+            pause: false,
         }
     }
     pub fn reset(&mut self) {
@@ -41,6 +46,26 @@ impl Inputs {
         self.down = false;
         self.fire = false;
         self.special = false;
+        self.pause = false;
+    }
+    // Packs the inputs into one byte, e.g. for recording replays.
+    pub fn to_bits(&self) -> u8 {
+        (self.left as u8)
+            | (self.right as u8) << 1
+            | (self.up as u8) << 2
+            | (self.down as u8) << 3
+            | (self.fire as u8) << 4
+            | (self.special as u8) << 5
+            | (self.pause as u8) << 6
+    }
+    pub fn set_bits(&mut self, bits: u8) {
+        self.left = bits & 1 != 0;
+        self.right = bits & 1 << 1 != 0;
+        self.up = bits & 1 << 2 != 0;
+        self.down = bits & 1 << 3 != 0;
+        self.fire = bits & 1 << 4 != 0;
+        self.special = bits & 1 << 5 != 0;
+        self.pause = bits & 1 << 6 != 0;
     }
 }
 
@@ -51,6 +76,8 @@ pub fn key_down_event(
     _keymod: Mod,
     repeat: bool,
 ) {
+    // Holding a key repeats it, but only the first press counts - otherwise holding Escape would
+    // keep opening and closing the pause menu.
     if repeat {
         return;
     }
@@ -62,6 +89,7 @@ pub fn key_down_event(
         Keycode::Right | Keycode::D => inputs.right = true,
         Keycode::Space => inputs.fire = true,
         Keycode::Return => inputs.special = true,
+        Keycode::Escape => inputs.pause = true,
         _ => (),
     };
 }
@@ -84,32 +112,42 @@ pub fn key_up_event(
         Keycode::Right | Keycode::D => inputs.right = false,
         Keycode::Space => inputs.fire = false,
         Keycode::Return => inputs.special = false,
+        Keycode::Escape => inputs.pause = false,
         _ => (),
     };
 }
 
 pub fn controller_button_down_event(
-    _world: &mut World,
+    world: &mut World,
     _ctx: &mut Context,
     btn: Button,
-    instance_id: i32,
+    _instance_id: i32,
 ) {
-    println!(
-        "Controller button pressed: {:?} Controller_Id: {}",
-        btn, instance_id
-    );
+    let mut inputs = world.write_resource::<Inputs>();
+    set_button(&mut inputs, btn, true);
 }
 
 pub fn controller_button_up_event(
-    _world: &mut World,
+    world: &mut World,
     _ctx: &mut Context,
     btn: Button,
-    instance_id: i32,
+    _instance_id: i32,
 ) {
-    println!(
-        "Controller button released: {:?} Controller_Id: {}",
-        btn, instance_id
-    );
+    let mut inputs = world.write_resource::<Inputs>();
+    set_button(&mut inputs, btn, false);
+}
+
+fn set_button(inputs: &mut Inputs, btn: Button, pressed: bool) {
+    match btn {
+        Button::DPadUp => inputs.up = pressed,
+        Button::DPadDown => inputs.down = pressed,
+        Button::DPadLeft => inputs.left = pressed,
+        Button::DPadRight => inputs.right = pressed,
+        Button::A => inputs.fire = pressed,
+        Button::B => inputs.special = pressed,
+        Button::Start => inputs.pause = pressed,
+        _ => (),
+    };
 }
 
 pub fn controller_axis_event(
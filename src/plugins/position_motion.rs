@@ -1,17 +1,30 @@
 use specs::*;
 use ggez::graphics::Rect;
+use rayon::prelude::*;
 use DeltaTime;
+use plugins::*;
 
-pub fn init<'a, 'b>(
-    world: &mut World,
-    dispatcher: DispatcherBuilder<'a, 'b>,
-) -> DispatcherBuilder<'a, 'b> {
-    world.register::<Position>();
-    world.register::<PositionBounds>();
-    world.register::<Velocity>();
-    dispatcher
-        .add(MotionSystem, "motion", &[])
-        .add(PositionBoundsSystem, "position_bounds", &[])
+pub struct PositionMotionPlugin;
+impl Plugin for PositionMotionPlugin {
+    fn name(&self) -> &'static str {
+        "position_motion"
+    }
+    fn setup(&self, world: &mut World) {
+        world.add_resource(Interpolation(0.0));
+        world.register::<Position>();
+        world.register::<PreviousPosition>();
+        world.register::<PositionBounds>();
+        world.register::<Velocity>();
+    }
+    fn systems<'a, 'b>(&self, systems: &mut SystemRegistry<'a, 'b>) {
+        systems.add(Stage::Motion, MotionSystem, "motion", &[]);
+        systems.add(
+            Stage::Motion,
+            PositionBoundsSystem,
+            "position_bounds",
+            &["motion"],
+        );
+    }
 }
 
 #[derive(Component, Debug)]
@@ -30,6 +43,49 @@ impl Default for Position {
     }
 }
 
+// Where an entity was at the start of the current tick, so rendering can blend between ticks.
+#[derive(Component, Debug)]
+pub struct PreviousPosition {
+    pub x: f32,
+    pub y: f32,
+    pub r: f32,
+}
+
+// Fraction of a fixed step that has elapsed since the last tick, from 0.0 to 1.0.
+#[derive(Debug)]
+pub struct Interpolation(pub f32);
+
+pub fn update_previous(world: &mut World) {
+    let entities = world.entities();
+    let positions = world.read::<Position>();
+    let mut previous_positions = world.write::<PreviousPosition>();
+    for (entity, pos) in (&*entities, &positions).join() {
+        previous_positions.insert(
+            entity,
+            PreviousPosition {
+                x: pos.x,
+                y: pos.y,
+                r: pos.r,
+            },
+        );
+    }
+}
+
+pub fn interpolate(
+    previous: Option<&PreviousPosition>,
+    current: &Position,
+    alpha: f32,
+) -> (f32, f32, f32) {
+    match previous {
+        Some(prev) => (
+            prev.x + (current.x - prev.x) * alpha,
+            prev.y + (current.y - prev.y) * alpha,
+            prev.r + (current.r - prev.r) * alpha,
+        ),
+        None => (current.x, current.y, current.r),
+    }
+}
+
 #[derive(Component, Debug)]
 pub struct PositionBounds(pub Rect);
 
@@ -68,11 +124,11 @@ impl<'a> System<'a> for MotionSystem {
     fn run(&mut self, data: Self::SystemData) {
         let (delta, vel, mut pos) = data;
         let delta = delta.0;
-        for (vel, pos) in (&vel, &mut pos).join() {
+        (&vel, &mut pos).par_join().for_each(|(vel, pos)| {
             pos.x += vel.x * delta;
             pos.y += vel.y * delta;
             pos.r += vel.r * delta;
-        }
+        });
     }
 }
 
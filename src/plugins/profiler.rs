@@ -0,0 +1,274 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+use specs::*;
+use ggez::*;
+use plugins::*;
+
+// How many samples the averages and percentiles are taken over.
+const WINDOW: usize = 240;
+// Oldest trace events are dropped past this, so leaving the profiler on doesn't eat memory.
+const MAX_TRACE_EVENTS: usize = 200_000;
+
+// Times every system, and anything else wrapped in `start` / `finish`. Does nothing but check a
+// flag until it's enabled.
+pub struct Profiler {
+    pub enabled: bool,
+    pub overlay: bool,
+    data: Mutex<ProfilerData>,
+}
+
+struct ProfilerData {
+    epoch: Instant,
+    timings: HashMap<&'static str, Timings>,
+    threads: HashMap<ThreadId, usize>,
+    trace: VecDeque<TraceEvent>,
+}
+
+struct TraceEvent {
+    name: &'static str,
+    thread: usize,
+    start: Duration,
+    duration: Duration,
+}
+
+// Rolling window of one thing's timings, in milliseconds.
+pub struct Timings {
+    pub count: u64,
+    recent: VecDeque<f32>,
+}
+impl Timings {
+    fn new() -> Timings {
+        Timings {
+            count: 0,
+            recent: VecDeque::with_capacity(WINDOW),
+        }
+    }
+    fn add(&mut self, ms: f32) {
+        if self.recent.len() == WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(ms);
+        self.count += 1;
+    }
+    pub fn average(&self) -> f32 {
+        if self.recent.is_empty() {
+            return 0.0;
+        }
+        self.recent.iter().sum::<f32>() / self.recent.len() as f32
+    }
+    // `fraction` from 0.0 to 1.0, e.g. 0.95 for the 95th percentile.
+    pub fn percentile(&self, fraction: f32) -> f32 {
+        if self.recent.is_empty() {
+            return 0.0;
+        }
+        let mut sorted: Vec<f32> = self.recent.iter().cloned().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let idx = ((sorted.len() - 1) as f32 * fraction).round() as usize;
+        sorted[idx]
+    }
+    pub fn max(&self) -> f32 {
+        self.recent.iter().cloned().fold(0.0, f32::max)
+    }
+}
+
+// One row of the summary, in milliseconds.
+pub struct Summary {
+    pub name: &'static str,
+    pub count: u64,
+    pub average: f32,
+    pub p50: f32,
+    pub p95: f32,
+    pub p99: f32,
+    pub max: f32,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler {
+            enabled: false,
+            overlay: false,
+            data: Mutex::new(ProfilerData {
+                epoch: Instant::now(),
+                timings: HashMap::new(),
+                threads: HashMap::new(),
+                trace: VecDeque::new(),
+            }),
+        }
+    }
+
+    pub fn start(&self) -> Option<Instant> {
+        if self.enabled {
+            Some(Instant::now())
+        } else {
+            None
+        }
+    }
+
+    pub fn finish(&self, name: &'static str, start: Option<Instant>) {
+        if let Some(start) = start {
+            self.record(name, start, start.elapsed());
+        }
+    }
+
+    fn record(&self, name: &'static str, start: Instant, duration: Duration) {
+        let mut data = self.data.lock().unwrap();
+        let ms = duration.as_secs() as f32 * 1000.0 + duration.subsec_nanos() as f32 * 1e-6;
+        data.timings
+            .entry(name)
+            .or_insert_with(Timings::new)
+            .add(ms);
+
+        let thread_count = data.threads.len();
+        let thread = *data
+            .threads
+            .entry(thread::current().id())
+            .or_insert(thread_count);
+        let since_epoch = start.duration_since(data.epoch);
+        if data.trace.len() == MAX_TRACE_EVENTS {
+            data.trace.pop_front();
+        }
+        data.trace.push_back(TraceEvent {
+            name,
+            thread,
+            start: since_epoch,
+            duration,
+        });
+    }
+
+    pub fn reset(&mut self) {
+        let data = self.data.get_mut().unwrap();
+        data.epoch = Instant::now();
+        data.timings.clear();
+        data.trace.clear();
+    }
+
+    // Slowest first, by average.
+    pub fn summary(&self) -> Vec<Summary> {
+        let data = self.data.lock().unwrap();
+        let mut rows: Vec<Summary> = data
+            .timings
+            .iter()
+            .map(|(&name, timings)| Summary {
+                name,
+                count: timings.count,
+                average: timings.average(),
+                p50: timings.percentile(0.5),
+                p95: timings.percentile(0.95),
+                p99: timings.percentile(0.99),
+                max: timings.max(),
+            })
+            .collect();
+        rows.sort_by(|a, b| b.average.total_cmp(&a.average));
+        rows
+    }
+
+    pub fn write_csv<P: AsRef<Path>>(&self, path: P) -> GameResult<()> {
+        let mut out = String::from("name,count,avg_ms,p50_ms,p95_ms,p99_ms,max_ms\n");
+        for row in self.summary() {
+            out.push_str(&format!(
+                "{},{},{:.4},{:.4},{:.4},{:.4},{:.4}\n",
+                row.name, row.count, row.average, row.p50, row.p95, row.p99, row.max
+            ));
+        }
+        File::create(path)?.write_all(out.as_bytes())?;
+        Ok(())
+    }
+
+    // Chrome's trace event format - open it in chrome://tracing or Perfetto.
+    pub fn write_trace<P: AsRef<Path>>(&self, path: P) -> GameResult<()> {
+        let data = self.data.lock().unwrap();
+        let mut out = String::from("{\"traceEvents\":[\n");
+        for (idx, event) in data.trace.iter().enumerate() {
+            if idx > 0 {
+                out.push_str(",\n");
+            }
+            out.push_str(&format!(
+                "{{\"name\":\"{}\",\"ph\":\"X\",\"pid\":1,\"tid\":{},\"ts\":{},\"dur\":{}}}",
+                event.name,
+                event.thread,
+                micros(event.start),
+                micros(event.duration)
+            ));
+        }
+        out.push_str("\n]}\n");
+        File::create(path)?.write_all(out.as_bytes())?;
+        Ok(())
+    }
+}
+impl Default for Profiler {
+    fn default() -> Profiler {
+        Profiler::new()
+    }
+}
+
+fn micros(duration: Duration) -> u64 {
+    duration.as_secs() * 1_000_000 + u64::from(duration.subsec_micros())
+}
+
+// Times something that needs the whole world, like a draw phase.
+pub fn start(world: &World) -> Option<Instant> {
+    world.read_resource::<Profiler>().start()
+}
+
+pub fn finish(world: &World, name: &'static str, start: Option<Instant>) {
+    world.read_resource::<Profiler>().finish(name, start);
+}
+
+pub fn toggle_overlay(world: &mut World) {
+    let mut profiler = world.write_resource::<Profiler>();
+    profiler.overlay = !profiler.overlay;
+    // No point showing the overlay with nothing to show.
+    if profiler.overlay {
+        profiler.enabled = true;
+    }
+}
+
+// Wraps each registered system, so the dispatcher times them all without them knowing.
+pub struct Profiled<S> {
+    pub name: &'static str,
+    pub system: S,
+}
+impl<'a, S: System<'a>> System<'a> for Profiled<S> {
+    type SystemData = (Fetch<'a, Profiler>, S::SystemData);
+    fn run(&mut self, data: Self::SystemData) {
+        let (profiler, data) = data;
+        let start = profiler.start();
+        self.system.run(data);
+        profiler.finish(self.name, start);
+    }
+}
+
+pub fn draw_overlay(
+    world: &mut World,
+    font: &mut fonts::Font,
+    ctx: &mut Context,
+) -> GameResult<()> {
+    let profiler = world.read_resource::<Profiler>();
+    if !profiler.overlay {
+        return Ok(());
+    }
+    let viewport_state = world.read_resource::<viewport::ViewportState>();
+    let mut text = String::from("                     avg    p95    max\n");
+    for row in profiler.summary().iter().take(24) {
+        text.push_str(&format!(
+            "{:<20} {:6.3} {:6.3} {:6.3}\n",
+            row.name, row.average, row.p95, row.max
+        ));
+    }
+    font.draw(
+        ctx,
+        &text,
+        fonts::DrawOptions {
+            x: viewport_state.screen.x + 25.0,
+            y: viewport_state.screen.y + 150.0,
+            scale: 1.0,
+            ..Default::default()
+        },
+    )?;
+    Ok(())
+}
@@ -0,0 +1,368 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use specs::*;
+use ggez::{GameError, GameResult};
+use plugins::*;
+
+const MAGIC: &[u8; 4] = b"RIRP";
+const FORMAT_VERSION: u8 = 3;
+pub const BUILD_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+pub struct ReplayPlugin;
+impl Plugin for ReplayPlugin {
+    fn name(&self) -> &'static str {
+        "replay"
+    }
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec!["player_control", "rng", "score"]
+    }
+    fn setup(&self, world: &mut World) {
+        world.add_resource(Replay::new());
+    }
+    fn systems<'a, 'b>(&self, systems: &mut SystemRegistry<'a, 'b>) {
+        systems.add(Stage::Input, ReplaySystem, "replay", &[]);
+    }
+}
+
+// Start recording from the current tick. The world should have been built with `seed` and the
+// game config given as TOML in `config`. If the session didn't start fresh, `start` is a snapshot
+// of the world as it is now.
+pub fn start_recording(world: &mut World, seed: u64, config: String, start: String) {
+    let mut replay = world.write_resource::<Replay>();
+    replay.record(seed, config, start);
+}
+
+// Writes out everything recorded so far, along with the score it ended on.
+pub fn save_recording<P: AsRef<Path>>(world: &mut World, path: P) -> GameResult<()> {
+    let mut replay = world.write_resource::<Replay>();
+    let player_score = world.read_resource::<score::PlayerScore>();
+    replay.recording.final_score = player_score.get();
+    replay.recording.save(path)
+}
+
+// Play back a recording from the current tick. The world should have been built with the
+// recording's seed and config, and restored from its start snapshot if it has one.
+pub fn start_playback(world: &mut World, recording: Recording) {
+    let mut replay = world.write_resource::<Replay>();
+    replay.play(recording);
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum ReplayMode {
+    Off,
+    Recording,
+    Playing,
+    Finished,
+}
+
+#[derive(Debug)]
+pub struct Replay {
+    pub mode: ReplayMode,
+    pub recording: Recording,
+    cursor: usize,
+}
+impl Replay {
+    pub fn new() -> Replay {
+        Replay {
+            mode: ReplayMode::Off,
+            recording: Recording::new(0, String::new(), String::new()),
+            cursor: 0,
+        }
+    }
+    pub fn record(&mut self, seed: u64, config: String, start: String) {
+        self.mode = ReplayMode::Recording;
+        self.recording = Recording::new(seed, config, start);
+        self.cursor = 0;
+    }
+    pub fn play(&mut self, recording: Recording) {
+        self.mode = ReplayMode::Playing;
+        self.recording = recording;
+        self.cursor = 0;
+    }
+    pub fn is_finished(&self) -> bool {
+        self.mode == ReplayMode::Finished
+    }
+}
+impl Default for Replay {
+    fn default() -> Replay {
+        Replay::new()
+    }
+}
+
+// A session's inputs, one byte per tick as packed by `Inputs::to_bits`. On disk, runs of
+// identical ticks are collapsed, since inputs rarely change from one tick to the next.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recording {
+    pub version: String,
+    pub seed: u64,
+    // The game config the session was played with, as TOML - the same inputs under different
+    // tunables play out differently.
+    pub config: String,
+    // A snapshot of the world when recording began, as TOML. Empty for a session that started
+    // fresh in attract mode.
+    pub start: String,
+    pub final_score: i32,
+    pub frames: Vec<u8>,
+}
+impl Recording {
+    pub fn new(seed: u64, config: String, start: String) -> Recording {
+        Recording {
+            version: String::from(BUILD_VERSION),
+            seed,
+            config,
+            start,
+            final_score: 0,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> GameResult<Recording> {
+        let mut data = Vec::new();
+        File::open(path)?.read_to_end(&mut data)?;
+        Recording::decode(&data)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> GameResult<()> {
+        File::create(path)?.write_all(&self.encode())?;
+        Ok(())
+    }
+
+    // magic, format version, build version (length-prefixed), seed, config and start snapshot
+    // (each with a 4 byte length prefix), final score, tick count, then (run length varint,
+    // input bits) pairs.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.push(FORMAT_VERSION);
+        let version = self.version.as_bytes();
+        let version = &version[..version.len().min(255)];
+        out.push(version.len() as u8);
+        out.extend_from_slice(version);
+        write_uint(&mut out, self.seed, 8);
+        write_text(&mut out, &self.config);
+        write_text(&mut out, &self.start);
+        write_uint(&mut out, self.final_score as u32 as u64, 4);
+        write_uint(&mut out, self.frames.len() as u64, 4);
+
+        let mut frames = self.frames.iter().peekable();
+        while let Some(&bits) = frames.next() {
+            let mut run = 1u32;
+            while frames.peek() == Some(&&bits) {
+                frames.next();
+                run += 1;
+            }
+            write_varint(&mut out, run);
+            out.push(bits);
+        }
+        out
+    }
+
+    pub fn decode(data: &[u8]) -> GameResult<Recording> {
+        let mut reader = ByteReader { data, pos: 0 };
+        if reader.take(4)? != MAGIC {
+            return Err(replay_error("not a replay file"));
+        }
+        let format_version = reader.byte()?;
+        if format_version != FORMAT_VERSION {
+            return Err(replay_error(&format!(
+                "unsupported replay format version {}",
+                format_version
+            )));
+        }
+        let version_len = reader.byte()? as usize;
+        let version = String::from_utf8_lossy(reader.take(version_len)?).into_owned();
+        let seed = reader.uint(8)?;
+        let config = reader.text()?;
+        let start = reader.text()?;
+        let final_score = reader.uint(4)? as u32 as i32;
+        let tick_count = reader.uint(4)? as usize;
+
+        // The count comes straight from the file, so it only gets as much room up front as the
+        // rest of the file could possibly fill.
+        let mut frames = Vec::with_capacity(tick_count.min(reader.remaining()));
+        while frames.len() < tick_count {
+            let run = reader.varint()? as usize;
+            let bits = reader.byte()?;
+            if run == 0 || frames.len() + run > tick_count {
+                return Err(replay_error("corrupt input run"));
+            }
+            let len = frames.len();
+            frames.resize(len + run, bits);
+        }
+
+        Ok(Recording {
+            version,
+            seed,
+            config,
+            start,
+            final_score,
+            frames,
+        })
+    }
+}
+
+pub struct ReplaySystem;
+impl<'a> System<'a> for ReplaySystem {
+    type SystemData = (FetchMut<'a, Replay>, FetchMut<'a, player_control::Inputs>);
+    fn run(&mut self, data: Self::SystemData) {
+        let (mut replay, mut inputs) = data;
+        match replay.mode {
+            ReplayMode::Recording => replay.recording.frames.push(inputs.to_bits()),
+            ReplayMode::Playing => {
+                // Recorded inputs replace whatever the keyboard did this tick.
+                let next = replay.recording.frames.get(replay.cursor).cloned();
+                match next {
+                    Some(bits) => {
+                        inputs.set_bits(bits);
+                        replay.cursor += 1;
+                    }
+                    None => {
+                        inputs.reset();
+                        replay.mode = ReplayMode::Finished;
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn replay_error(message: &str) -> GameError {
+    GameError::ResourceLoadError(format!("Bad replay: {}", message))
+}
+
+// Little-endian unsigned int of `len` bytes.
+fn write_uint(out: &mut Vec<u8>, value: u64, len: usize) {
+    for idx in 0..len {
+        out.push((value >> (idx * 8)) as u8);
+    }
+}
+
+// UTF-8 with a 4 byte length prefix.
+fn write_text(out: &mut Vec<u8>, text: &str) {
+    write_uint(out, text.len() as u64, 4);
+    out.extend_from_slice(text.as_bytes());
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, len: usize) -> GameResult<&'a [u8]> {
+        if self.pos + len > self.data.len() {
+            return Err(replay_error("unexpected end of file"));
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+    fn byte(&mut self) -> GameResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+    // Little-endian unsigned int of `len` bytes.
+    fn uint(&mut self, len: usize) -> GameResult<u64> {
+        let bytes = self.take(len)?;
+        Ok(bytes
+            .iter()
+            .enumerate()
+            .fold(0, |acc, (idx, &byte)| acc | (byte as u64) << (idx * 8)))
+    }
+    fn text(&mut self) -> GameResult<String> {
+        let len = self.uint(4)? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| replay_error("bad text"))
+    }
+    fn varint(&mut self) -> GameResult<u32> {
+        let mut value = 0u32;
+        for shift in 0..5 {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u32) << (shift * 7);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(replay_error("run length too long"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording(frames: Vec<u8>) -> Recording {
+        let mut recording = Recording::new(
+            0xdead_beef_1234_5678,
+            String::from("[player]\nthrust = 12000.0\n"),
+            String::new(),
+        );
+        recording.final_score = -5;
+        recording.frames = frames;
+        recording
+    }
+
+    #[test]
+    fn round_trips() {
+        // Runs either side of where the run length needs another byte, plus runs of one.
+        let mut frames = Vec::new();
+        for &(bits, run) in &[(1u8, 1), (2, 127), (3, 128), (0, 1), (4, 16383), (5, 16384)] {
+            frames.extend(vec![bits; run]);
+        }
+        frames.extend(&[1, 2, 1, 2]);
+        let long = recording(frames);
+        assert_eq!(Recording::decode(&long.encode()).unwrap(), long);
+
+        let empty = recording(Vec::new());
+        assert_eq!(Recording::decode(&empty.encode()).unwrap(), empty);
+    }
+
+    #[test]
+    fn truncated_files_are_errors() {
+        let data = recording(vec![1, 1, 1, 2, 3, 3]).encode();
+        for len in 0..data.len() {
+            assert!(
+                Recording::decode(&data[..len]).is_err(),
+                "decoded {} of {} bytes",
+                len,
+                data.len()
+            );
+        }
+    }
+
+    #[test]
+    fn corrupt_files_are_errors() {
+        let mut data = recording(vec![7; 3]).encode();
+        // A single run of 3 ticks, claiming 4 instead.
+        let run = data.len() - 2;
+        data[run] = 4;
+        assert!(Recording::decode(&data).is_err());
+        data[run] = 0;
+        assert!(Recording::decode(&data).is_err());
+
+        let mut data = recording(Vec::new()).encode();
+        data[0] = b'X';
+        assert!(Recording::decode(&data).is_err());
+    }
+
+    #[test]
+    fn huge_tick_counts_fail_without_allocating() {
+        let mut data = recording(vec![1]).encode();
+        // The tick count sits just before the one run.
+        let count = data.len() - 6;
+        for byte in &mut data[count..count + 4] {
+            *byte = 0xff;
+        }
+        assert!(Recording::decode(&data).is_err());
+    }
+}
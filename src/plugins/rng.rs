@@ -0,0 +1,88 @@
+use rand;
+use rand::Rng;
+use specs::*;
+use plugins::Plugin;
+
+// Mixed into the seed so the cosmetic stream never mirrors the gameplay one.
+const COSMETIC_SALT: u64 = 0x5DEE_CE66_D1CE_4E5B;
+
+pub struct RngPlugin {
+    pub seed: u64,
+}
+impl RngPlugin {
+    pub fn new(seed: u64) -> RngPlugin {
+        RngPlugin { seed }
+    }
+}
+impl Plugin for RngPlugin {
+    fn name(&self) -> &'static str {
+        "rng"
+    }
+    fn setup(&self, world: &mut World) {
+        world.add_resource(GameRng::new(self.seed));
+    }
+}
+
+// A seed for when none was asked for. Report it somewhere, or the session can't be reproduced.
+pub fn random_seed() -> u64 {
+    rand::random::<u64>()
+}
+
+// All randomness in the game comes from here. Anything that affects the simulation draws from
+// `gameplay`; things that only affect what's drawn or heard - and so may run a different number
+// of times per tick depending on frame rate - draw from `cosmetic`, so they can't knock the
+// gameplay stream out of step.
+#[derive(Clone, Debug)]
+pub struct GameRng {
+    pub seed: u64,
+    pub gameplay: RngStream,
+    pub cosmetic: RngStream,
+}
+impl GameRng {
+    pub fn new(seed: u64) -> GameRng {
+        GameRng {
+            seed,
+            gameplay: RngStream::new(seed),
+            cosmetic: RngStream::new(seed ^ COSMETIC_SALT),
+        }
+    }
+    pub fn reseed(&mut self, seed: u64) {
+        *self = GameRng::new(seed);
+    }
+}
+
+// xorshift64* - tiny, fast, and its whole state is one u64 that's easy to save and restore.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RngStream {
+    state: u64,
+}
+impl RngStream {
+    pub fn new(seed: u64) -> RngStream {
+        // Run the seed through splitmix64 so that nearby seeds give unrelated streams.
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        RngStream::from_state(z)
+    }
+    pub fn from_state(state: u64) -> RngStream {
+        // xorshift gets stuck at zero forever
+        RngStream {
+            state: if state == 0 { 1 } else { state },
+        }
+    }
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+}
+impl Rng for RngStream {
+    fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
@@ -1,18 +1,44 @@
+use std::collections::{btree_map, BTreeMap};
 use specs::*;
 use plugins::*;
 use DeltaTime;
 
-pub fn init<'a, 'b>(
-    world: &mut World,
-    dispatcher: DispatcherBuilder<'a, 'b>,
-) -> DispatcherBuilder<'a, 'b> {
-    world.add_resource(PlayerScore::new());
-    world.register::<PointsOnLastHit>();
-    dispatcher.add(ScoreSystem, "score_system", &[]).add(
-        PointsOnLastHitSystem,
-        "points_on_last_hit",
-        &[],
-    )
+pub struct ScorePlugin;
+impl Plugin for ScorePlugin {
+    fn name(&self) -> &'static str {
+        "score"
+    }
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec!["despawn", "health_damage", "metadata", "sprites"]
+    }
+    fn setup(&self, world: &mut World) {
+        world.add_resource(PlayerScore::new());
+        world.add_resource(Scores::default());
+        world
+            .write_resource::<despawn::DespawnEventQueue>()
+            .register_reader("points_on_last_hit");
+        world.register::<PointsOnLastHit>();
+        world.register::<ScoresPoints>();
+    }
+    fn systems<'a, 'b>(&self, systems: &mut SystemRegistry<'a, 'b>) {
+        // Points are only awarded once everything that might despawn an entity this tick has run.
+        systems.add(
+            Stage::Despawn,
+            PointsOnLastHitSystem,
+            "points_on_last_hit",
+            &[
+                "timeout_system",
+                "despawn_bounds_system",
+                "despawn_on_collision_system",
+            ],
+        );
+        systems.add(
+            Stage::Despawn,
+            ScoreSystem,
+            "score_system",
+            &["points_on_last_hit"],
+        );
+    }
 }
 
 pub struct ScoreSystem;
@@ -26,38 +52,71 @@ impl<'a> System<'a> for ScoreSystem {
 
 #[derive(Component, Debug)]
 pub struct PointsOnLastHit(pub i32);
+
+// Kills and assists by this, or anything it owns, go on the score in the given slot.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct ScoresPoints(pub usize);
+
+// The player's slot is kept in PlayerScore, the one the HUD and high scores use.
+pub const PLAYER_ONE: usize = 0;
+
+// Helping destroy something without landing the last hit is worth this share of its points.
+const ASSIST_SHARE: f32 = 0.5;
+
 pub struct PointsOnLastHitSystem;
 impl<'a> System<'a> for PointsOnLastHitSystem {
     type SystemData = (
         Entities<'a>,
         FetchMut<'a, PlayerScore>,
+        FetchMut<'a, Scores>,
         Fetch<'a, despawn::DespawnEventQueue>,
         ReadStorage<'a, PointsOnLastHit>,
         ReadStorage<'a, health_damage::Health>,
-        ReadStorage<'a, metadata::Tags>,
-        ReadStorage<'a, sprites::Sprite>,
+        ReadStorage<'a, ScoresPoints>,
     );
     fn run(&mut self, data: Self::SystemData) {
         let (
-            _entities,
+            entities,
             mut player_score,
+            mut scores,
             despawn_events,
             points_on_last_hits,
             healths,
-            tags,
-            _sprites,
+            scorers,
         ) = data;
-        for despawn_event in &despawn_events.0 {
+        // Credit is only good while whoever earned it is still around - once an entity is
+        // gone, its id may belong to something else.
+        let slot = |entity: Entity| {
+            if entities.is_alive(entity) {
+                scorers.get(entity).map(|scorer| scorer.0)
+            } else {
+                None
+            }
+        };
+        for despawn_event in despawn_events.read("points_on_last_hit") {
             let entity = despawn_event.entity;
             if let (Some(points), Some(health)) =
                 (points_on_last_hits.get(entity), healths.get(entity))
             {
-                if let Some(last_hurt_by) = health.last_hurt_by {
-                    if let Some(tags) = tags.get(last_hurt_by) {
-                        // TODO: stop hardcoding these strings, use Enum for tags
-                        if tags.0.contains(&"player") || tags.0.contains(&"player_weapon") {
-                            player_score.increment(points.0);
+                let credits = health
+                    .last_hurt_by
+                    .into_iter()
+                    .map(|killer| (killer, 1.0))
+                    .chain(
+                        health
+                            .assists()
+                            .into_iter()
+                            .map(|assist| (assist, ASSIST_SHARE)),
+                    );
+                for (scorer, share) in credits {
+                    match slot(scorer) {
+                        // The multiplier comes from the player's power-ups.
+                        Some(PLAYER_ONE) => {
+                            let points = points.0 * player_score.multiplier();
+                            player_score.increment((points as f32 * share) as i32);
                         }
+                        Some(other) => scores.increment(other, (points.0 as f32 * share) as i32),
+                        None => {}
                     }
                 }
             }
@@ -65,11 +124,34 @@ impl<'a> System<'a> for PointsOnLastHitSystem {
     }
 }
 
+// Running totals for every score slot other than the player's.
+#[derive(Debug, Default)]
+pub struct Scores(BTreeMap<usize, i32>);
+impl Scores {
+    pub fn get(&self, slot: usize) -> i32 {
+        self.0.get(&slot).cloned().unwrap_or(0)
+    }
+    pub fn set(&mut self, slot: usize, score: i32) {
+        self.0.insert(slot, score);
+    }
+    pub fn increment(&mut self, slot: usize, amount: i32) {
+        *self.0.entry(slot).or_insert(0) += amount;
+    }
+    pub fn iter<'a>(&'a self) -> btree_map::Iter<'a, usize, i32> {
+        self.0.iter()
+    }
+    pub fn reset(&mut self) {
+        self.0.clear();
+    }
+}
+
 #[derive(Debug)]
 pub struct PlayerScore {
     current: i32,
     displayed: i32,
     factor: i32,
+    // Points awarded for destroying things are multiplied by this.
+    multiplier: i32,
 }
 impl PlayerScore {
     pub fn new() -> PlayerScore {
@@ -77,11 +159,13 @@ impl PlayerScore {
             current: 0,
             displayed: 0,
             factor: 10,
+            multiplier: 1,
         }
     }
     pub fn reset(&mut self) {
         self.current = 0;
         self.displayed = 0;
+        self.multiplier = 1;
     }
     pub fn reset_displayed(&mut self) {
         self.displayed = 0;
@@ -92,6 +176,12 @@ impl PlayerScore {
     pub fn set(&mut self, new_score: i32) {
         self.current = new_score;
     }
+    pub fn multiplier(&self) -> i32 {
+        self.multiplier
+    }
+    pub fn set_multiplier(&mut self, multiplier: i32) {
+        self.multiplier = multiplier;
+    }
     pub fn increment(&mut self, amount: i32) {
         self.current += amount;
     }
@@ -104,6 +194,13 @@ impl PlayerScore {
     pub fn get_displayed(&self) -> i32 {
         self.displayed
     }
+    pub fn set_displayed(&mut self, displayed: i32) {
+        self.displayed = displayed;
+    }
+    // True once the displayed score has caught up with the real one.
+    pub fn is_tallied(&self) -> bool {
+        self.displayed == self.current
+    }
     pub fn update(&mut self) {
         if self.displayed == self.current {
             return;
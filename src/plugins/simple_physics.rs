@@ -1,5 +1,6 @@
 use specs::*;
 use ggez::graphics::Vector2;
+use rayon::prelude::*;
 use DeltaTime;
 use plugins::*;
 
@@ -11,15 +12,22 @@ pub struct SpeedLimit(pub f32);
 
 pub struct SpeedLimitSystem;
 
-pub fn init<'a, 'b>(
-    world: &mut World,
-    dispatcher: DispatcherBuilder<'a, 'b>,
-) -> DispatcherBuilder<'a, 'b> {
-    world.register::<Friction>();
-    world.register::<SpeedLimit>();
-    dispatcher
-        .add(SpeedLimitSystem, "speed_limit", &[])
-        .add(FrictionSystem, "friction", &[])
+pub struct SimplePhysicsPlugin;
+impl Plugin for SimplePhysicsPlugin {
+    fn name(&self) -> &'static str {
+        "simple_physics"
+    }
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec!["position_motion"]
+    }
+    fn setup(&self, world: &mut World) {
+        world.register::<Friction>();
+        world.register::<SpeedLimit>();
+    }
+    fn systems<'a, 'b>(&self, systems: &mut SystemRegistry<'a, 'b>) {
+        systems.add(Stage::Physics, SpeedLimitSystem, "speed_limit", &[]);
+        systems.add(Stage::Physics, FrictionSystem, "friction", &[]);
+    }
 }
 
 // TODO: Use nalgebra or move this to a util module
@@ -37,20 +45,22 @@ impl<'a> System<'a> for SpeedLimitSystem {
 
     fn run(&mut self, data: Self::SystemData) {
         let (speed_limit, mut velocity) = data;
-        for (speed_limit, velocity) in (&speed_limit, &mut velocity).join() {
-            let speed_limit = speed_limit.0;
-            let m_inertia = (velocity.x * velocity.x + velocity.y * velocity.y).sqrt();
-            if m_inertia <= speed_limit {
-                continue;
-            }
+        (&speed_limit, &mut velocity)
+            .par_join()
+            .for_each(|(speed_limit, velocity)| {
+                let speed_limit = speed_limit.0;
+                let m_inertia = (velocity.x * velocity.x + velocity.y * velocity.y).sqrt();
+                if m_inertia <= speed_limit {
+                    return;
+                }
 
-            let a_inertia = velocity.x.atan2(velocity.y);
-            let v_limit = vec_from_angle(a_inertia) * speed_limit;
+                let a_inertia = velocity.x.atan2(velocity.y);
+                let v_limit = vec_from_angle(a_inertia) * speed_limit;
 
-            // TODO: Rework so speed limit is applied as thrust, rather than assignment.
-            velocity.x = v_limit.x;
-            velocity.y = v_limit.y;
-        }
+                // TODO: Rework so speed limit is applied as thrust, rather than assignment.
+                velocity.x = v_limit.x;
+                velocity.y = v_limit.y;
+            });
     }
 }
 
@@ -66,19 +76,21 @@ impl<'a> System<'a> for FrictionSystem {
     fn run(&mut self, data: Self::SystemData) {
         let (delta, friction, mut velocity) = data;
         let delta = delta.0;
-        for (friction, velocity) in (&friction, &mut velocity).join() {
-            if velocity.x == 0.0 && velocity.y == 0.0 {
-                continue;
-            }
+        (&friction, &mut velocity)
+            .par_join()
+            .for_each(|(friction, velocity)| {
+                if velocity.x == 0.0 && velocity.y == 0.0 {
+                    return;
+                }
 
-            let friction = friction.0;
-            let m_inertia = (velocity.x * velocity.x + velocity.y * velocity.y).sqrt();
-            let a_inertia = velocity.x.atan2(velocity.y);
-            let m_braking = 0.0 - m_inertia.min(friction * delta);
-            let v_braking = vec_from_angle(a_inertia) * m_braking;
+                let friction = friction.0;
+                let m_inertia = (velocity.x * velocity.x + velocity.y * velocity.y).sqrt();
+                let a_inertia = velocity.x.atan2(velocity.y);
+                let m_braking = 0.0 - m_inertia.min(friction * delta);
+                let v_braking = vec_from_angle(a_inertia) * m_braking;
 
-            velocity.x += v_braking.x;
-            velocity.y += v_braking.y;
-        }
+                velocity.x += v_braking.x;
+                velocity.y += v_braking.y;
+            });
     }
 }
@@ -3,18 +3,22 @@ use std::collections::{HashMap, HashSet};
 use specs::*;
 use ggez::*;
 use ggez::graphics::{DrawMode, DrawParam, Mesh, MeshBuilder, Point2};
-use rand;
 use rand::Rng;
 
 use plugins::*;
 
-pub fn init<'a, 'b>(
-    world: &mut World,
-    dispatcher: DispatcherBuilder<'a, 'b>,
-) -> DispatcherBuilder<'a, 'b> {
-    world.add_resource(SpriteCache::new());
-    world.register::<Sprite>();
-    dispatcher
+pub struct SpritesPlugin;
+impl Plugin for SpritesPlugin {
+    fn name(&self) -> &'static str {
+        "sprites"
+    }
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec!["position_motion", "rng"]
+    }
+    fn setup(&self, world: &mut World) {
+        world.add_resource(SpriteCache::new());
+        world.register::<Sprite>();
+    }
 }
 
 pub struct SpriteCache(pub HashMap<Entity, Mesh>);
@@ -32,9 +36,12 @@ impl Default for SpriteCache {
 pub fn draw(world: &mut World, ctx: &mut Context) -> GameResult<()> {
     let entities = world.entities();
     let positions = world.read::<position_motion::Position>();
+    let previous_positions = world.read::<position_motion::PreviousPosition>();
+    let alpha = world.read_resource::<position_motion::Interpolation>().0;
     let sprites = world.read::<Sprite>();
     let _viewport_state = world.read_resource::<viewport::ViewportState>();
     let mut sprite_cache = world.write_resource::<SpriteCache>();
+    let mut rng = world.write_resource::<rng::GameRng>();
 
     let mut seen_entities: HashSet<Entity> = HashSet::new();
 
@@ -46,15 +53,16 @@ pub fn draw(world: &mut World, ctx: &mut Context) -> GameResult<()> {
         let mesh = sprite_cache
             .0
             .entry(ent)
-            .or_insert_with(|| shape.build_mesh(ctx, line_width));
+            .or_insert_with(|| shape.build_mesh(ctx, line_width, &mut rng.cosmetic));
         seen_entities.insert(ent);
 
+        let (x, y, r) = position_motion::interpolate(previous_positions.get(ent), pos, alpha);
         graphics::draw_ex(
             ctx,
             &*mesh,
             DrawParam {
-                dest: Point2::new(pos.x, pos.y),
-                rotation: pos.r,
+                dest: Point2::new(x, y),
+                rotation: r,
                 offset: spr.offset,
                 scale: spr.scale,
                 ..Default::default()
@@ -89,7 +97,7 @@ impl Default for Sprite {
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum Shape {
     Test,
     Player,
@@ -98,19 +106,34 @@ pub enum Shape {
     SimpleBullet,
     Planet,
     PlanetIcon,
+    PowerUp,
 }
 impl Shape {
-    pub fn build_mesh(&self, ctx: &mut Context, line_width: f32) -> Mesh {
+    pub fn build_mesh<R: Rng>(&self, ctx: &mut Context, line_width: f32, rng: &mut R) -> Mesh {
         match *self {
             Shape::Explosion => explosion(ctx, line_width),
             Shape::Player => player(ctx, line_width),
-            Shape::Asteroid => asteroid(ctx, line_width),
+            Shape::Asteroid => asteroid(ctx, line_width, rng),
             Shape::SimpleBullet => simple_bullet(ctx, line_width),
-            Shape::Planet => planet(ctx, line_width),
+            Shape::Planet => planet(ctx, line_width, rng),
             Shape::PlanetIcon => planet_icon(ctx, line_width),
+            Shape::PowerUp => power_up(ctx, line_width),
             _ => test(ctx, line_width),
         }
     }
+
+    // Points around the edge of the shapes that don't come out different every time, in the same
+    // 0.0 to 1.0 space as the meshes.
+    pub fn outline(&self) -> Option<&'static [(f32, f32)]> {
+        match *self {
+            Shape::Test => Some(TEST_OUTLINE),
+            Shape::Player => Some(PLAYER_OUTLINE),
+            Shape::Explosion => Some(EXPLOSION_OUTLINE),
+            Shape::SimpleBullet => Some(SIMPLE_BULLET_OUTLINE),
+            Shape::PowerUp => Some(POWER_UP_OUTLINE),
+            _ => None,
+        }
+    }
 }
 impl Default for Shape {
     fn default() -> Shape {
@@ -118,6 +141,45 @@ impl Default for Shape {
     }
 }
 
+const TEST_OUTLINE: &[(f32, f32)] = &[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+
+const PLAYER_OUTLINE: &[(f32, f32)] = &[
+    (0.5, 0.0),
+    (0.4375, 0.0),
+    (0.25, 0.5),
+    (0.125, 0.67),
+    (0.25, 1.0),
+    (0.375, 1.0),
+    (0.4375, 0.75),
+    (0.5625, 0.75),
+    (0.625, 1.0),
+    (0.75, 1.0),
+    (0.875, 0.67),
+    (0.75, 0.5),
+    (0.5625, 0.0),
+    (0.5, 0.0),
+];
+
+const EXPLOSION_OUTLINE: &[(f32, f32)] = &[
+    (0.5, 0.0),
+    (0.55, 0.45),
+    (1.0, 0.5),
+    (0.55, 0.55),
+    (0.5, 1.0),
+    (0.45, 0.55),
+    (0.0, 0.5),
+    (0.45, 0.45),
+];
+
+const SIMPLE_BULLET_OUTLINE: &[(f32, f32)] =
+    &[(0.5, 0.0), (0.6, 0.25), (0.5, 1.0), (0.4, 0.25), (0.5, 0.0)];
+
+const POWER_UP_OUTLINE: &[(f32, f32)] = &[(0.5, 0.0), (1.0, 0.5), (0.5, 1.0), (0.0, 0.5)];
+
+fn outline_points(outline: &[(f32, f32)]) -> Vec<Point2> {
+    outline.iter().map(|&(x, y)| Point2::new(x, y)).collect()
+}
+
 // TODO: Figure out if there's a better way to write this macro
 macro_rules! points {
     ( $( $x:expr ), * ) => {
@@ -131,10 +193,7 @@ macro_rules! points {
 
 pub fn test(ctx: &mut Context, line_width: f32) -> Mesh {
     MeshBuilder::new()
-        .polygon(
-            DrawMode::Line(line_width),
-            &points![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)],
-        )
+        .polygon(DrawMode::Line(line_width), &outline_points(TEST_OUTLINE))
         .polygon(
             DrawMode::Line(line_width),
             &points![(0.5, 0.0), (1.0, 1.0), (0.0, 1.0)],
@@ -158,7 +217,7 @@ pub fn planet_icon(ctx: &mut Context, line_width: f32) -> Mesh {
         .unwrap()
 }
 
-pub fn planet(ctx: &mut Context, line_width: f32) -> Mesh {
+pub fn planet<R: Rng>(ctx: &mut Context, line_width: f32, rng: &mut R) -> Mesh {
     let mut num_points = 100.0;
     let max_radius = 0.4975;
     let min_radius = 0.45;
@@ -167,7 +226,7 @@ pub fn planet(ctx: &mut Context, line_width: f32) -> Mesh {
     let mut points = Vec::new();
     let mut rotation: f32 = 0.0;
     loop {
-        let distance = rand::thread_rng().gen_range(min_radius, max_radius);
+        let distance = rng.gen_range(min_radius, max_radius);
         points.push(Point2::new(
             0.5 - distance * rotation.cos(),
             0.5 - distance * rotation.sin(),
@@ -195,58 +254,43 @@ pub fn explosion(ctx: &mut Context, line_width: f32) -> Mesh {
     MeshBuilder::new()
         .polygon(
             DrawMode::Line(line_width),
-            &points![
-                (0.5, 0.0),
-                (0.55, 0.45),
-                (1.0, 0.5),
-                (0.55, 0.55),
-                (0.5, 1.0),
-                (0.45, 0.55),
-                (0.0, 0.5),
-                (0.45, 0.45)
-            ],
+            &outline_points(EXPLOSION_OUTLINE),
         )
         .build(ctx)
         .unwrap()
 }
 
 pub fn player(ctx: &mut Context, line_width: f32) -> Mesh {
+    MeshBuilder::new()
+        .polygon(DrawMode::Line(line_width), &outline_points(PLAYER_OUTLINE))
+        .build(ctx)
+        .unwrap()
+}
+
+pub fn simple_bullet(ctx: &mut Context, line_width: f32) -> Mesh {
     MeshBuilder::new()
         .polygon(
             DrawMode::Line(line_width),
-            &points![
-                (0.5, 0.0),
-                (0.4375, 0.0),
-                (0.25, 0.5),
-                (0.125, 0.67),
-                (0.25, 1.0),
-                (0.375, 1.0),
-                (0.4375, 0.75),
-                (0.5625, 0.75),
-                (0.625, 1.0),
-                (0.75, 1.0),
-                (0.875, 0.67),
-                (0.75, 0.5),
-                (0.5625, 0.0),
-                (0.5, 0.0)
-            ],
+            &outline_points(SIMPLE_BULLET_OUTLINE),
         )
         .build(ctx)
         .unwrap()
 }
 
-pub fn simple_bullet(ctx: &mut Context, line_width: f32) -> Mesh {
+pub fn power_up(ctx: &mut Context, line_width: f32) -> Mesh {
     MeshBuilder::new()
         .polygon(
             DrawMode::Line(line_width),
-            &points![(0.5, 0.0), (0.6, 0.25), (0.5, 1.0), (0.4, 0.25), (0.5, 0.0)],
+            &outline_points(POWER_UP_OUTLINE),
         )
+        .circle(DrawMode::Line(line_width), Point2::new(0.5, 0.5), 0.2, 0.05)
+        .line(&points![(0.5, 0.3), (0.5, 0.7)], line_width)
         .build(ctx)
         .unwrap()
 }
 
-pub fn asteroid(ctx: &mut Context, line_width: f32) -> Mesh {
-    let mut num_points = 7.0 + rand::thread_rng().gen_range(0.0, 12.0);
+pub fn asteroid<R: Rng>(ctx: &mut Context, line_width: f32, rng: &mut R) -> Mesh {
+    let mut num_points = 7.0 + rng.gen_range(0.0, 12.0);
     let max_radius = 0.5;
     let min_radius = 0.3;
     let rotation_step = (PI * 2.0) / num_points;
@@ -254,7 +298,7 @@ pub fn asteroid(ctx: &mut Context, line_width: f32) -> Mesh {
     let mut points = Vec::new();
     let mut rotation: f32 = 0.0;
     loop {
-        let distance = rand::thread_rng().gen_range(min_radius, max_radius);
+        let distance = rng.gen_range(min_radius, max_radius);
         points.push(Point2::new(
             0.5 - distance * rotation.cos(),
             0.5 - distance * rotation.sin(),
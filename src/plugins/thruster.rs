@@ -1,19 +1,26 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::f32::consts::PI;
 use ggez::graphics::Vector2;
 use specs::*;
 use plugins::*;
 use DeltaTime;
 
-pub fn init<'a, 'b>(
-    world: &mut World,
-    dispatcher: DispatcherBuilder<'a, 'b>,
-) -> DispatcherBuilder<'a, 'b> {
-    world.register::<Thruster>();
-    world.register::<ThrusterSet>();
-    dispatcher
-        .add(ThrusterSystem, "thruster", &[])
-        .add(ThrusterSetSystem, "thruster_set", &[])
+pub struct ThrusterPlugin;
+impl Plugin for ThrusterPlugin {
+    fn name(&self) -> &'static str {
+        "thruster"
+    }
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec!["position_motion"]
+    }
+    fn setup(&self, world: &mut World) {
+        world.register::<Thruster>();
+        world.register::<ThrusterSet>();
+    }
+    fn systems<'a, 'b>(&self, systems: &mut SystemRegistry<'a, 'b>) {
+        systems.add(Stage::Physics, ThrusterSystem, "thruster", &[]);
+        systems.add(Stage::Physics, ThrusterSetSystem, "thruster_set", &[]);
+    }
 }
 
 #[derive(Component, Debug)]
@@ -24,7 +31,7 @@ pub struct Thruster {
 }
 
 #[derive(Component, Debug)]
-pub struct ThrusterSet(pub HashMap<&'static str, Thruster>);
+pub struct ThrusterSet(pub BTreeMap<&'static str, Thruster>);
 
 // TODO: Use nalgebra or move this to a util module
 fn vec_from_angle(angle: f32) -> Vector2 {
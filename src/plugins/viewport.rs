@@ -2,21 +2,26 @@ use specs::*;
 use ggez::*;
 use ggez::graphics::*;
 use ::*;
-use rand;
+use rand::Rng;
 
+// Defaults - the game sets its own size from the config.
 pub const PLAYFIELD_WIDTH: f32 = 1600.0;
 pub const PLAYFIELD_HEIGHT: f32 = 900.0;
-pub const PLAYFIELD_RATIO: f32 = PLAYFIELD_WIDTH / PLAYFIELD_HEIGHT;
 
-pub fn init<'a, 'b>(
-    world: &mut World,
-    dispatcher: DispatcherBuilder<'a, 'b>,
-) -> DispatcherBuilder<'a, 'b> {
-    world.add_resource(ViewportState::new());
-    dispatcher
+pub struct ViewportPlugin;
+impl plugins::Plugin for ViewportPlugin {
+    fn name(&self) -> &'static str {
+        "viewport"
+    }
+    fn dependencies(&self) -> Vec<&'static str> {
+        vec!["rng"]
+    }
+    fn setup(&self, world: &mut World) {
+        world.add_resource(ViewportState::new());
+    }
 }
 
-pub fn update(world: &mut World, _ctx: &mut Context) -> GameResult<()> {
+pub fn update(world: &mut World) -> GameResult<()> {
     let mut viewport_state = world.write_resource::<ViewportState>();
     let delta = world.read_resource::<DeltaTime>();
     if viewport_state.shake_duration > 0.0 {
@@ -27,11 +32,13 @@ pub fn update(world: &mut World, _ctx: &mut Context) -> GameResult<()> {
 
 pub fn draw(world: &mut World, ctx: &mut Context) -> GameResult<()> {
     let viewport_state = world.read_resource::<ViewportState>();
+    let control = world.read_resource::<SimulationControl>();
+    let mut rng = world.write_resource::<plugins::rng::GameRng>();
     let screen = viewport_state.screen;
     let mut coords = Rect::new(screen.x, screen.y, screen.w, screen.h);
-    if viewport_state.shake_duration > 0.0 {
-        coords.x += (0.5 - rand::random::<f32>()) * viewport_state.shake;
-        coords.y += (0.5 - rand::random::<f32>()) * viewport_state.shake;
+    if viewport_state.shake_duration > 0.0 && !control.paused {
+        coords.x += (0.5 - rng.cosmetic.gen::<f32>()) * viewport_state.shake;
+        coords.y += (0.5 - rng.cosmetic.gen::<f32>()) * viewport_state.shake;
     }
     graphics::set_screen_coordinates(ctx, coords)?;
     Ok(())
@@ -43,6 +50,8 @@ pub struct ViewportState {
     pub zoom: f32,
     pub shake: f32,
     pub shake_duration: f32,
+    pub playfield_width: f32,
+    pub playfield_height: f32,
 }
 impl ViewportState {
     pub fn new() -> ViewportState {
@@ -51,11 +60,18 @@ impl ViewportState {
             zoom: 1.0,
             shake: 0.0,
             shake_duration: 0.0,
+            playfield_width: PLAYFIELD_WIDTH,
+            playfield_height: PLAYFIELD_HEIGHT,
         }
     }
     pub fn set_screen(&mut self, screen: Rect) {
         self.screen = screen;
     }
+    // Takes effect on the next update_screen.
+    pub fn set_playfield(&mut self, width: f32, height: f32) {
+        self.playfield_width = width;
+        self.playfield_height = height;
+    }
     pub fn shake(&mut self, shake: f32, shake_duration: f32) {
         self.shake = shake;
         self.shake_duration = shake_duration;
@@ -81,10 +97,10 @@ impl ViewportState {
     }
     pub fn update_screen(&mut self, width: f32, height: f32) {
         let screen_ratio = width / height;
-        let fit_ratio = if screen_ratio < PLAYFIELD_RATIO {
-            PLAYFIELD_WIDTH / width
+        let fit_ratio = if screen_ratio < self.playfield_width / self.playfield_height {
+            self.playfield_width / width
         } else {
-            PLAYFIELD_HEIGHT / height
+            self.playfield_height / height
         };
 
         let (visible_width, visible_height) = (
@@ -0,0 +1,116 @@
+use specs::*;
+use ggez::*;
+use plugins::*;
+use {DeltaTime, SimulationControl};
+
+/// Every tick of the simulation advances time by exactly this much, whatever the frame rate.
+pub const FIXED_STEP: f32 = 1.0 / 120.0;
+
+/// Upper bound on ticks run to catch up after a long frame, so a stall can't snowball into an
+/// ever-growing backlog of steps.
+pub const MAX_CATCH_UP_STEPS: u32 = 8;
+
+/// Owns the World and Dispatcher and ticks them without needing a ggez Context, so whole game
+/// sessions can be run with no window or GPU.
+pub struct Simulation<'a, 'b> {
+    pub world: World,
+    dispatcher: StagedDispatcher<'a, 'b>,
+    plugins: PluginSet,
+    accumulator: f32,
+    pub ticks: u64,
+    pub elapsed: f32,
+}
+
+impl<'a, 'b> Simulation<'a, 'b> {
+    /// Builds a simulation from a set of plugins, e.g. `game_plugins()` for the full game.
+    pub fn new(mut plugins: PluginSet) -> Result<Simulation<'a, 'b>, PluginError> {
+        let mut world = World::new();
+        let dispatcher = plugins.build_staged(&mut world)?;
+        let mut simulation = Simulation::from_parts(world, dispatcher);
+        simulation.plugins = plugins;
+        Ok(simulation)
+    }
+
+    /// Builds a simulation from a World and dispatcher that were set up by hand.
+    pub fn from_parts(world: World, dispatcher: StagedDispatcher<'a, 'b>) -> Simulation<'a, 'b> {
+        Simulation {
+            world,
+            dispatcher,
+            plugins: PluginSet::new(),
+            accumulator: 0.0,
+            ticks: 0,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Accumulates a frame's worth of real time and runs as many fixed steps as it covers. The
+    /// leftover fraction of a step is published as `Interpolation` for rendering.
+    pub fn update(&mut self, frame_delta: f32) -> GameResult<()> {
+        self.accumulator += frame_delta;
+        let mut steps = 0;
+        while self.accumulator >= FIXED_STEP {
+            if steps >= MAX_CATCH_UP_STEPS {
+                // Too far behind - drop the backlog rather than trying to catch up.
+                self.accumulator = 0.0;
+                break;
+            }
+            self.step(FIXED_STEP)?;
+            self.accumulator -= FIXED_STEP;
+            steps += 1;
+        }
+        // Nothing moves while paused, so draw everything where it is.
+        let alpha = if self.is_paused() {
+            1.0
+        } else {
+            self.accumulator / FIXED_STEP
+        };
+        let mut interpolation = self
+            .world
+            .write_resource::<position_motion::Interpolation>();
+        *interpolation = position_motion::Interpolation(alpha);
+        Ok(())
+    }
+
+    /// Advances the world by a single tick of `dt` seconds. While paused, only the stages that
+    /// run while paused get a look in.
+    pub fn step(&mut self, dt: f32) -> GameResult<()> {
+        let tick_start = profiler::start(&self.world);
+        *self.world.write_resource::<DeltaTime>() = DeltaTime(dt);
+        let paused = self.is_paused();
+        if paused {
+            self.dispatcher
+                .dispatch_stages(&self.world, |stage| stage.runs_while_paused());
+        } else {
+            position_motion::update_previous(&mut self.world);
+            viewport::update(&mut self.world)?;
+            self.dispatcher.dispatch(&self.world);
+        }
+        // Plugin updates get to see this tick's despawns before the entities go away.
+        let start = profiler::start(&self.world);
+        self.plugins.update(&mut self.world)?;
+        profiler::finish(&self.world, "plugin_updates", start);
+        if !paused {
+            despawn::update(&mut self.world)?;
+        }
+        let start = profiler::start(&self.world);
+        self.world.maintain();
+        profiler::finish(&self.world, "maintain", start);
+        profiler::finish(&self.world, "tick", tick_start);
+        self.ticks += 1;
+        self.elapsed += dt;
+        Ok(())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.world.read_resource::<SimulationControl>().paused
+    }
+
+    /// Runs however many fixed steps it takes to simulate `seconds` of game time.
+    pub fn run_for(&mut self, seconds: f32) -> GameResult<()> {
+        let steps = (seconds / FIXED_STEP).round() as u64;
+        for _ in 0..steps {
+            self.step(FIXED_STEP)?;
+        }
+        Ok(())
+    }
+}